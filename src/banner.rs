@@ -0,0 +1,102 @@
+use crate::editor::width;
+use crate::interpreter::Value;
+use crate::prompt::{self, PromptInfo};
+use crate::shell::Shell;
+
+// the sosh function run before every prompt, if there is one, to change the banner
+pub const HOOK: &str = "update_banner";
+
+const STYLE: &str = "\x1b[1m\x1b[37m\x1b[100m";
+
+// The bar under the line being typed: three segments that go on its left, in its middle and
+// on its right. They're written in the prompt language, `\?` for the last status and so on,
+// until `expand` replaces the escapes for showing them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Banner {
+    pub left: String,
+    pub center: String,
+    pub right: String,
+}
+
+impl Default for Banner {
+    fn default() -> Self {
+        Banner {
+            left: r"[\?] \D".to_string(),
+            center: r"\g".to_string(),
+            right: r"jobs: \j  \t".to_string(),
+        }
+    }
+}
+
+impl Banner {
+    pub fn expand(&self, info: &PromptInfo) -> Banner {
+        Banner {
+            left: prompt::expand(&self.left, info),
+            center: prompt::expand(&self.center, info),
+            right: prompt::expand(&self.right, info),
+        }
+    }
+
+    // The segments laid out on a row `columns` wide, leaving the last column empty so the row
+    // never wraps. The left one comes first when there isn't room for all of them, then the
+    // right one, and the middle one only shows when it fits without touching either.
+    pub fn layout(&self, columns: usize) -> String {
+        if columns < 3 {
+            return String::new();
+        }
+        // a space at both ends, like the search bar has, and the last column left empty
+        let inner = columns - 3;
+        let (left, left_width) = width::truncate(&self.left, inner);
+        let gap = if left_width > 0 { 1 } else { 0 };
+        let (right, right_width) =
+            width::truncate(&self.right, inner.saturating_sub(left_width + gap));
+
+        // segments are followed by the banner's style again, in case they changed colours
+        let mut out = format!("{} {}{}", STYLE, left, STYLE);
+        let mut used = left_width;
+        let center_width = width::display_width(&self.center);
+        if center_width > 0 {
+            // centred on the row when possible, otherwise just after the left segment
+            let start = (inner.saturating_sub(center_width) / 2).max(left_width + gap);
+            let end = start + center_width;
+            if end <= inner && (right_width == 0 || end < inner - right_width) {
+                out.push_str(&" ".repeat(start - used));
+                out.push_str(&self.center);
+                out.push_str(STYLE);
+                used = end;
+            }
+        }
+        if right_width > 0 {
+            out.push_str(&" ".repeat(inner - used - right_width));
+            out.push_str(right);
+            out.push_str(STYLE);
+            used = inner;
+        }
+        out.push_str(&" ".repeat(inner - used));
+        out.push_str(" \x1b[0m");
+        out
+    }
+}
+
+// The banner to show with the next prompt, after running the `update_banner` function when
+// there is one, or nothing when `set +o banner` turned it off.
+pub fn render(shell: &mut Shell) -> Option<Banner> {
+    let interpreter = &mut shell.interpreter;
+    if let Some(function @ Value::Function(_)) = interpreter.get(HOOK) {
+        // like with the prompt, what the hook runs doesn't count as the last command
+        let status = interpreter.last_status;
+        if let Err(error) = interpreter.call(&function, Vec::new()) {
+            eprintln!("Error: {}: {}", HOOK, error);
+        }
+        interpreter.last_status = status;
+    }
+    if !interpreter.options.banner {
+        return None;
+    }
+    Some(
+        shell
+            .banner
+            .borrow()
+            .expand(&PromptInfo::gather(interpreter)),
+    )
+}
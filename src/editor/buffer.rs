@@ -0,0 +1,158 @@
+use crate::editor::width::is_combining;
+
+// The text being edited and the cursor, a byte offset that always sits on a character
+// boundary and never between a character and the combining marks that follow it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LineBuffer {
+    text: String,
+    cursor: usize,
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+impl LineBuffer {
+    pub fn new(text: &str) -> Self {
+        LineBuffer {
+            text: text.to_string(),
+            cursor: text.len(),
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    // the text left of the cursor
+    pub fn before_cursor(&self) -> &str {
+        &self.text[..self.cursor]
+    }
+
+    pub fn set_cursor(&mut self, cursor: usize) {
+        self.cursor = cursor.min(self.text.len());
+        while !self.text.is_char_boundary(self.cursor) {
+            self.cursor -= 1;
+        }
+    }
+
+    pub fn insert(&mut self, c: char) {
+        self.text.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+    }
+
+    pub fn insert_str(&mut self, text: &str) {
+        self.text.insert_str(self.cursor, text);
+        self.cursor += text.len();
+    }
+
+    // start of the character (with its combining marks) before `position`
+    pub fn previous(&self, position: usize) -> usize {
+        for (index, c) in self.text[..position].char_indices().rev() {
+            if !is_combining(c) || index == 0 {
+                return index;
+            }
+        }
+        0
+    }
+
+    // end of the character (with its combining marks) after `position`
+    pub fn next(&self, position: usize) -> usize {
+        for (index, c) in self.text[position..].char_indices().skip(1) {
+            if !is_combining(c) {
+                return position + index;
+            }
+        }
+        self.text.len()
+    }
+
+    // where a backward word motion from `position` lands
+    fn word_start(&self, position: usize, is_word: fn(char) -> bool) -> usize {
+        let before = &self.text[..position];
+        let trimmed = before.trim_end_matches(|c| !is_word(c));
+        trimmed.trim_end_matches(is_word).len()
+    }
+
+    // where a forward word motion from `position` lands
+    fn word_end(&self, position: usize, is_word: fn(char) -> bool) -> usize {
+        let after = &self.text[position..];
+        let trimmed = after.trim_start_matches(|c| !is_word(c));
+        let rest = trimmed.trim_start_matches(is_word);
+        self.text.len() - rest.len()
+    }
+
+    pub fn move_left(&mut self) {
+        self.cursor = self.previous(self.cursor);
+    }
+
+    pub fn move_right(&mut self) {
+        if self.cursor < self.text.len() {
+            self.cursor = self.next(self.cursor);
+        }
+    }
+
+    pub fn move_home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn move_end(&mut self) {
+        self.cursor = self.text.len();
+    }
+
+    pub fn move_word_left(&mut self) {
+        self.cursor = self.word_start(self.cursor, is_word);
+    }
+
+    pub fn move_word_right(&mut self) {
+        self.cursor = self.word_end(self.cursor, is_word);
+    }
+
+    // removes `start..end` and leaves the cursor where the text was, returning what was cut
+    pub fn cut(&mut self, start: usize, end: usize) -> String {
+        self.cursor = start;
+        self.text.drain(start..end).collect()
+    }
+
+    pub fn delete_before(&mut self) -> String {
+        self.cut(self.previous(self.cursor), self.cursor)
+    }
+
+    pub fn delete_after(&mut self) -> String {
+        if self.cursor == self.text.len() {
+            return String::new();
+        }
+        self.cut(self.cursor, self.next(self.cursor))
+    }
+
+    // Ctrl-W: back to the previous whitespace, so `ls /usr/lo` loses the whole path
+    pub fn delete_word_before(&mut self) -> String {
+        let start = self.word_start(self.cursor, |c| !c.is_whitespace());
+        self.cut(start, self.cursor)
+    }
+
+    // Alt-Backspace: back to the start of the previous alphanumeric word
+    pub fn delete_alnum_word_before(&mut self) -> String {
+        self.cut(self.word_start(self.cursor, is_word), self.cursor)
+    }
+
+    pub fn delete_word_after(&mut self) -> String {
+        let end = self.word_end(self.cursor, is_word);
+        self.cut(self.cursor, end)
+    }
+
+    pub fn delete_to_start(&mut self) -> String {
+        self.cut(0, self.cursor)
+    }
+
+    pub fn delete_to_end(&mut self) -> String {
+        self.cut(self.cursor, self.text.len())
+    }
+}
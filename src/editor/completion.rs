@@ -0,0 +1,263 @@
+use crate::editor::width::display_width;
+use crate::interpreter::commands::parse_alias;
+use crate::interpreter::exec;
+use crate::interpreter::word;
+use crate::interpreter::{Interpreter, Value};
+use crate::shell::Shell;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+// What a completer gets to know about the word being completed.
+#[derive(Clone, Debug)]
+pub struct CompletionContext {
+    // the whole line and the cursor, a byte offset into it
+    pub line: String,
+    pub cursor: usize,
+    // the words of the command before the one being completed, starting with the command
+    // name, and that word up to the cursor, all with quoting undone
+    pub words: Vec<String>,
+    pub word: String,
+}
+
+// Offers candidates for the arguments of one command, registered with
+// `Shell::set_completer` or the `complete` builtin. Candidates that don't start with the
+// word being completed are dropped, and when none are left, file names are offered instead.
+pub trait Completer {
+    fn complete(&self, interpreter: &mut Interpreter, context: &CompletionContext) -> Vec<String>;
+
+    // what `complete` lists this completer as
+    fn describe(&self) -> String {
+        "(native)".to_string()
+    }
+}
+
+impl<F> Completer for F
+where
+    F: Fn(&mut Interpreter, &CompletionContext) -> Vec<String>,
+{
+    fn complete(&self, interpreter: &mut Interpreter, context: &CompletionContext) -> Vec<String> {
+        self(interpreter, context)
+    }
+}
+
+// A sosh function used as a completer. It's called with the word, the words before it joined
+// by spaces and the cursor position, or as many of those as it takes, and returns its
+// candidates in a string separated by whitespace.
+pub struct FunctionCompleter(pub Value);
+
+impl Completer for FunctionCompleter {
+    fn complete(&self, interpreter: &mut Interpreter, context: &CompletionContext) -> Vec<String> {
+        let Value::Function(function) = &self.0 else {
+            return Vec::new();
+        };
+        let cursor = context.line[..context.cursor].chars().count();
+        let mut arguments = vec![
+            Value::String(context.word.clone()),
+            Value::String(context.words.join(" ")),
+            Value::Number(cursor as f64),
+        ];
+        arguments.truncate(function.params.len());
+
+        match interpreter.call(&self.0, arguments) {
+            Ok(Value::String(candidates)) => {
+                candidates.split_whitespace().map(String::from).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match &self.0 {
+            Value::Function(function) => function.name.clone(),
+            value => value.to_string(),
+        }
+    }
+}
+
+impl Shell {
+    pub fn set_completer(&self, command: &str, completer: impl Completer + 'static) {
+        self.completers
+            .borrow_mut()
+            .insert(command.to_string(), Rc::new(completer));
+    }
+
+    // The completer for the arguments of the command in `words`, with the words as it gets to
+    // see them: a command without a completer of its own is looked up again as what its alias
+    // stands for.
+    pub fn completer(&self, words: &[String]) -> Option<(Rc<dyn Completer>, Vec<String>)> {
+        let command = words.first()?;
+        let completers = self.completers.borrow();
+        if let Some(completer) = completers.get(command) {
+            return Some((completer.clone(), words.to_vec()));
+        }
+
+        let lookup = |name: &str| self.interpreter.lookup_variable(name);
+        let mut expanded: Vec<String> = parse_alias(self.interpreter.aliases.get(command)?)?
+            .iter()
+            .map(|raw| unquote(raw, &lookup).0)
+            .collect();
+        expanded.extend_from_slice(&words[1..]);
+        let completer = completers.get(expanded.first()?)?.clone();
+        Some((completer, expanded))
+    }
+}
+
+// characters that end a command word, start quoting or expansion, or make it a pattern, and so
+// need a backslash outside quotes
+const SPECIAL: &[char] = &[
+    ' ', '\t', '\n', ';', '|', '&', '<', '>', '\'', '"', '\\', '$', '*', '?', '[', '}', '~', '!',
+];
+
+// The words of the command the cursor is in, up to the cursor, as raw text with their start
+// offsets. The last one is the word being typed, empty right after a space.
+pub fn command_words(before_cursor: &str) -> Vec<(usize, &str)> {
+    word::split_commands(before_cursor)
+        .pop()
+        .unwrap_or_default()
+}
+
+// The word the cursor is at the end of: where it starts in `before_cursor`, and whether it's
+// the first word of a command.
+pub fn current_word(before_cursor: &str) -> (usize, bool) {
+    let words = command_words(before_cursor);
+    (words[words.len() - 1].0, words.len() == 1)
+}
+
+// Undoes the quoting of a word being typed, substituting variables and a leading `~` as
+// running it would. Returns the text and the quote still open at its end.
+pub fn unquote(raw: &str, lookup: &dyn Fn(&str) -> Option<String>) -> (String, Option<char>) {
+    let mut text = String::new();
+    let mut chars = raw.chars().peekable();
+    if raw == "~" || raw.starts_with("~/") {
+        chars.next();
+        text.push_str(&lookup("HOME").unwrap_or_else(|| "~".to_string()));
+    }
+
+    let mut quote = None;
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\'', None) | ('"', None) => quote = Some(c),
+            (c, Some(open)) if c == open => quote = None,
+            ('\\', None) => text.extend(chars.next()),
+            ('\\', Some('"')) => match chars.peek() {
+                Some(&escaped @ ('$' | '"' | '\\' | '`')) => {
+                    chars.next();
+                    text.push(escaped);
+                }
+                _ => text.push('\\'),
+            },
+            ('$', None | Some('"')) => {
+                let mut name = String::new();
+                if chars.next_if_eq(&'{').is_some() {
+                    name.extend(chars.by_ref().take_while(|&c| c != '}'));
+                } else {
+                    while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                        name.push(c);
+                    }
+                }
+                text.push_str(&lookup(&name).unwrap_or_default());
+            }
+            _ => text.push(c),
+        }
+    }
+    (text, quote)
+}
+
+// Writes `text` so the tokenizer reads it back unchanged inside the quote that's open, or with
+// backslashes when there's none.
+pub fn quote(text: &str, quote: Option<char>) -> String {
+    let mut quoted = String::new();
+    for c in text.chars() {
+        match quote {
+            Some('\'') if c == '\'' => quoted.push_str("'\\''"),
+            Some('"') if matches!(c, '$' | '"' | '\\' | '`') => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            None if SPECIAL.contains(&c) => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            _ => quoted.push(c),
+        }
+    }
+    quoted
+}
+
+// The entries of the directory `path` names whose names continue its last component, with a
+// `/` after directories. Hidden files only show up when that component starts with a dot, and
+// with `programs` only directories and executables do.
+pub fn path_candidates(path: &str, programs: bool) -> Vec<String> {
+    let (directory, prefix) = match path.rfind('/') {
+        Some(slash) => (&path[..=slash], &path[slash + 1..]),
+        None => ("", path),
+    };
+    let Ok(entries) = fs::read_dir(if directory.is_empty() { "." } else { directory }) else {
+        return Vec::new();
+    };
+
+    let mut candidates: Vec<String> = entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| name.starts_with(prefix))
+        .filter(|name| !name.starts_with('.') || prefix.starts_with('.'))
+        .filter_map(|name| {
+            let full = Path::new(directory).join(&name);
+            if full.is_dir() {
+                Some(name + "/")
+            } else if !programs || exec::is_executable(&full) {
+                Some(name)
+            } else {
+                None
+            }
+        })
+        .collect();
+    candidates.sort();
+    candidates
+}
+
+// the longest prefix every candidate shares
+pub fn common_prefix(candidates: &[String]) -> &str {
+    let Some(first) = candidates.first() else {
+        return "";
+    };
+    let mut length = first.len();
+    for candidate in &candidates[1..] {
+        length = first[..length]
+            .char_indices()
+            .zip(candidate.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(length.min(candidate.len()), |((offset, _), _)| offset);
+    }
+    &first[..length]
+}
+
+// Lays `candidates` out in columns like `ls` does, top to bottom and then left to right, in as
+// many columns as fit in `columns`. Returns the rows of the menu.
+pub fn menu_rows(candidates: &[String], columns: usize) -> Vec<String> {
+    let widest = candidates
+        .iter()
+        .map(|candidate| display_width(candidate))
+        .max()
+        .unwrap_or(0);
+    let column_width = widest + 2;
+    // the last screen column stays free so a full row never wraps
+    let per_row = (columns.saturating_sub(1) / column_width).max(1);
+    let rows = candidates.len().div_ceil(per_row);
+
+    (0..rows)
+        .map(|row| {
+            let mut line = String::new();
+            let mut cells = (row..candidates.len()).step_by(rows).peekable();
+            while let Some(index) = cells.next() {
+                let candidate = &candidates[index];
+                line.push_str(candidate);
+                if cells.peek().is_some() {
+                    line.push_str(&" ".repeat(column_width - display_width(candidate)));
+                }
+            }
+            line
+        })
+        .collect()
+}
@@ -0,0 +1,220 @@
+use crate::editor::completion;
+use crate::interpreter::token_kind::{
+    KeywordTokenKind, LiteralTokenKind, OperatorTokenKind, PunctuationTokenKind, Token, TokenKind,
+};
+use crate::interpreter::tokenizer::Tokenizer;
+use std::fmt::Write as _;
+use std::ops::Range;
+
+// The colours of the input line as SGR parameters, e.g. `1;35` for bold magenta. An empty one
+// leaves that kind of token as typed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    pub keyword: String,
+    pub operator: String,
+    pub punctuation: String,
+    pub string: String,
+    pub number: String,
+    pub identifier: String,
+    // arguments of commands
+    pub word: String,
+    // command names that resolve to a builtin, alias, function or program, and those that don't
+    pub command: String,
+    pub missing: String,
+    pub comment: String,
+    // unterminated strings and braces without their other half
+    pub error: String,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            keyword: "1;35".to_string(),
+            operator: "36".to_string(),
+            punctuation: String::new(),
+            string: "33".to_string(),
+            number: "34".to_string(),
+            identifier: String::new(),
+            word: String::new(),
+            command: "32".to_string(),
+            missing: "31".to_string(),
+            comment: "90".to_string(),
+            error: "4;31".to_string(),
+        }
+    }
+}
+
+impl Theme {
+    // The default theme with the colours `spec` sets, written like `LS_COLORS`:
+    // `keyword=1;35:string=33:missing=1;31`. Unknown names are ignored.
+    pub fn parse(spec: &str) -> Theme {
+        let mut theme = Theme::default();
+        for (name, colour) in spec.split(':').filter_map(|entry| entry.split_once('=')) {
+            let slot = match name.trim() {
+                "keyword" => &mut theme.keyword,
+                "operator" => &mut theme.operator,
+                "punctuation" => &mut theme.punctuation,
+                "string" => &mut theme.string,
+                "number" => &mut theme.number,
+                "identifier" => &mut theme.identifier,
+                "word" => &mut theme.word,
+                "command" => &mut theme.command,
+                "missing" => &mut theme.missing,
+                "comment" => &mut theme.comment,
+                "error" => &mut theme.error,
+                _ => continue,
+            };
+            *slot = colour.trim().to_string();
+        }
+        theme
+    }
+}
+
+// Colours `line` token by token. `is_command` tells whether the raw text of a word in command
+// position names something that can run. Whitespace and anything the tokenizer skipped are
+// copied as they are, so the result has the display width of the line.
+pub fn highlight(line: &str, theme: &Theme, is_command: &dyn Fn(&str) -> bool) -> String {
+    let tokens = Tokenizer::new(line).tokenize_with_spans();
+    let mut styles: Vec<&str> = Vec::with_capacity(tokens.len());
+    // openers not closed yet, as token indices
+    let mut open: Vec<usize> = Vec::new();
+    let mut command_position = true;
+    let mut redirect_target = false;
+
+    for (index, (token, span)) in tokens.iter().enumerate() {
+        let text = &line[span.clone()];
+        let mut next_is_command = false;
+        let style = match token.kind {
+            TokenKind::Keyword(keyword) => {
+                next_is_command = matches!(keyword, KeywordTokenKind::Else | KeywordTokenKind::Do);
+                &theme.keyword
+            }
+            TokenKind::Operator(operator) => {
+                next_is_command =
+                    matches!(operator, OperatorTokenKind::Or | OperatorTokenKind::And);
+                &theme.operator
+            }
+            TokenKind::Punctuation(punctuation) => {
+                next_is_command = matches!(
+                    punctuation,
+                    PunctuationTokenKind::Newline
+                        | PunctuationTokenKind::Semicolon
+                        | PunctuationTokenKind::LBrace
+                        | PunctuationTokenKind::RBrace
+                );
+                if balance(punctuation, index, &tokens, &mut open) {
+                    &theme.punctuation
+                } else {
+                    &theme.error
+                }
+            }
+            TokenKind::Literal(LiteralTokenKind::String) => {
+                let quote = text.chars().next();
+                if text.len() > 1 && text.chars().last() == quote {
+                    &theme.string
+                } else {
+                    &theme.error
+                }
+            }
+            TokenKind::Literal(LiteralTokenKind::Number) => &theme.number,
+            TokenKind::Literal(LiteralTokenKind::Identifier) => &theme.identifier,
+            TokenKind::Literal(LiteralTokenKind::Word) => {
+                if completion::unquote(text, &|_| None).1.is_some() {
+                    &theme.error
+                } else if redirect_target || !command_position {
+                    &theme.word
+                } else if is_assignment(text) {
+                    // `FOO=bar cmd` still has its command to come
+                    next_is_command = true;
+                    &theme.word
+                } else if is_command(text) {
+                    &theme.command
+                } else {
+                    &theme.missing
+                }
+            }
+            TokenKind::Comment => {
+                next_is_command = command_position;
+                &theme.comment
+            }
+            _ => "",
+        };
+        // a redirection's target doesn't change what the next word is
+        if !redirect_target || token.kind != TokenKind::Literal(LiteralTokenKind::Word) {
+            command_position = next_is_command;
+        }
+        redirect_target = is_redirect(token.kind);
+        styles.push(style);
+    }
+    for index in open {
+        styles[index] = &theme.error;
+    }
+
+    let mut out = String::new();
+    let mut last = 0;
+    for ((token, span), style) in tokens.iter().zip(styles) {
+        // here-document bodies come from later lines, and line breaks may jump past them
+        if span.start < last || token.kind == TokenKind::Literal(LiteralTokenKind::HereDoc) {
+            continue;
+        }
+        out.push_str(&line[last..span.start]);
+        let text = &line[span.clone()];
+        if style.is_empty() || text.is_empty() {
+            out.push_str(text);
+        } else {
+            let _ = write!(out, "\x1b[{}m{}\x1b[0m", style, text);
+        }
+        last = span.end;
+    }
+    out.push_str(&line[last..]);
+    out
+}
+
+// Keeps track of brackets, returning false for a closer that doesn't match the last opener.
+fn balance(
+    punctuation: PunctuationTokenKind,
+    index: usize,
+    tokens: &[(Token, Range<usize>)],
+    open: &mut Vec<usize>,
+) -> bool {
+    use PunctuationTokenKind::*;
+    let opener = match punctuation {
+        LParen | LBrace | LBracket => {
+            open.push(index);
+            return true;
+        }
+        RParen => LParen,
+        RBrace => LBrace,
+        RBracket => LBracket,
+        _ => return true,
+    };
+    match open.last() {
+        Some(&last) if tokens[last].0.kind == TokenKind::Punctuation(opener) => {
+            open.pop();
+            true
+        }
+        _ => false,
+    }
+}
+
+fn is_redirect(kind: TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::Operator(
+            OperatorTokenKind::Less
+                | OperatorTokenKind::DoubleLess
+                | OperatorTokenKind::TripleLess
+                | OperatorTokenKind::Greater
+                | OperatorTokenKind::DoubleGreater
+                | OperatorTokenKind::TripleGreater
+        )
+    )
+}
+
+fn is_assignment(word: &str) -> bool {
+    word.split_once('=').is_some_and(|(name, _)| {
+        let mut chars = name.chars();
+        matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
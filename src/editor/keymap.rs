@@ -0,0 +1,305 @@
+use crate::editor::keys::Key;
+use std::collections::{BTreeMap, HashMap};
+
+pub const EMACS: &str = "emacs";
+pub const VI_INSERT: &str = "vi-insert";
+// normal mode of vi, whose keys nobody bound make up vi commands
+pub const VI_COMMAND: &str = "vi-command";
+
+// What the line editor can do in response to a key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    AcceptLine,
+    // throws the line away, like Ctrl-C does in other shells
+    Interrupt,
+    // ends input on an empty line, otherwise deletes the character under the cursor
+    DeleteCharOrEof,
+    DeleteChar,
+    BackwardDeleteChar,
+    BackwardChar,
+    ForwardChar,
+    BeginningOfLine,
+    EndOfLine,
+    BackwardWord,
+    ForwardWord,
+    // kills back to the previous whitespace
+    UnixWordRubout,
+    // kills back to the start of the alphanumeric word
+    BackwardKillWord,
+    KillWord,
+    UnixLineDiscard,
+    KillLine,
+    Yank,
+    ClearScreen,
+    PreviousHistory,
+    NextHistory,
+    ReverseSearchHistory,
+    Complete,
+    // leaves vi insert mode
+    ViMovementMode,
+}
+
+// every action under the name `bind` knows it by
+const ACTIONS: &[(&str, Action)] = &[
+    ("accept-line", Action::AcceptLine),
+    ("backward-char", Action::BackwardChar),
+    ("backward-delete-char", Action::BackwardDeleteChar),
+    ("backward-kill-word", Action::BackwardKillWord),
+    ("backward-word", Action::BackwardWord),
+    ("beginning-of-line", Action::BeginningOfLine),
+    ("clear-screen", Action::ClearScreen),
+    ("complete", Action::Complete),
+    ("delete-char", Action::DeleteChar),
+    ("delete-char-or-eof", Action::DeleteCharOrEof),
+    ("end-of-line", Action::EndOfLine),
+    ("forward-char", Action::ForwardChar),
+    ("forward-word", Action::ForwardWord),
+    ("interrupt", Action::Interrupt),
+    ("kill-line", Action::KillLine),
+    ("kill-word", Action::KillWord),
+    ("next-history", Action::NextHistory),
+    ("previous-history", Action::PreviousHistory),
+    ("reverse-search-history", Action::ReverseSearchHistory),
+    ("unix-line-discard", Action::UnixLineDiscard),
+    ("unix-word-rubout", Action::UnixWordRubout),
+    ("vi-movement-mode", Action::ViMovementMode),
+    ("yank", Action::Yank),
+];
+
+impl Action {
+    pub fn from_name(name: &str) -> Option<Action> {
+        ACTIONS
+            .iter()
+            .find(|(action, _)| *action == name)
+            .map(|(_, action)| *action)
+    }
+
+    pub fn name(self) -> &'static str {
+        ACTIONS
+            .iter()
+            .find(|(_, action)| *action == self)
+            .map_or("", |(name, _)| name)
+    }
+
+    pub fn names() -> impl Iterator<Item = &'static str> {
+        ACTIONS.iter().map(|(name, _)| *name)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Binding {
+    Action(Action),
+    // a line of sosh to run, leaving the line being edited as it is
+    Command(String),
+}
+
+// What the keys pressed so far are bound to.
+#[derive(Debug, PartialEq)]
+pub enum Lookup<'a> {
+    Bound(&'a Binding),
+    // the start of a longer sequence, so wait for the next key
+    Prefix,
+    Unbound,
+}
+
+// Key sequences and what they do. Printable characters nobody bound insert themselves.
+#[derive(Clone, Debug, Default)]
+pub struct Keymap {
+    bindings: HashMap<Vec<Key>, Binding>,
+}
+
+impl Keymap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bind(&mut self, keys: Vec<Key>, binding: Binding) {
+        self.bindings.insert(keys, binding);
+    }
+
+    pub fn unbind(&mut self, keys: &[Key]) -> Option<Binding> {
+        self.bindings.remove(keys)
+    }
+
+    // A sequence that is bound wins over longer ones starting with it, there's no waiting to
+    // see which one was meant.
+    pub fn lookup(&self, keys: &[Key]) -> Lookup<'_> {
+        if let Some(binding) = self.bindings.get(keys) {
+            return Lookup::Bound(binding);
+        }
+        if self
+            .bindings
+            .keys()
+            .any(|bound| bound.len() > keys.len() && bound.starts_with(keys))
+        {
+            Lookup::Prefix
+        } else {
+            Lookup::Unbound
+        }
+    }
+
+    // the bindings with their sequences written out, sorted by those
+    pub fn bindings(&self) -> Vec<(String, &Binding)> {
+        let mut bindings: Vec<(String, &Binding)> = self
+            .bindings
+            .iter()
+            .map(|(keys, binding)| (format_keys(keys), binding))
+            .collect();
+        bindings.sort_by(|a, b| a.0.cmp(&b.0));
+        bindings
+    }
+
+    fn with(mut self, keys: &[Key], action: Action) -> Self {
+        for &key in keys {
+            self.bind(vec![key], Binding::Action(action));
+        }
+        self
+    }
+
+    // the keys both editing modes share while typing
+    fn basic() -> Self {
+        Keymap::new()
+            .with(&[Key::Enter, Key::Ctrl('j')], Action::AcceptLine)
+            .with(&[Key::Ctrl('c')], Action::Interrupt)
+            .with(&[Key::Ctrl('d')], Action::DeleteCharOrEof)
+            .with(&[Key::Delete], Action::DeleteChar)
+            .with(&[Key::Backspace], Action::BackwardDeleteChar)
+            .with(&[Key::Left], Action::BackwardChar)
+            .with(&[Key::Right], Action::ForwardChar)
+            .with(&[Key::Home], Action::BeginningOfLine)
+            .with(&[Key::End], Action::EndOfLine)
+            .with(&[Key::CtrlLeft], Action::BackwardWord)
+            .with(&[Key::CtrlRight], Action::ForwardWord)
+            .with(&[Key::Ctrl('w')], Action::UnixWordRubout)
+            .with(&[Key::Ctrl('u')], Action::UnixLineDiscard)
+            .with(&[Key::Up], Action::PreviousHistory)
+            .with(&[Key::Down], Action::NextHistory)
+            .with(&[Key::Ctrl('r')], Action::ReverseSearchHistory)
+            .with(&[Key::Tab], Action::Complete)
+    }
+
+    pub fn emacs() -> Self {
+        Keymap::basic()
+            .with(&[Key::Ctrl('b')], Action::BackwardChar)
+            .with(&[Key::Ctrl('f')], Action::ForwardChar)
+            .with(&[Key::Ctrl('a')], Action::BeginningOfLine)
+            .with(&[Key::Ctrl('e')], Action::EndOfLine)
+            .with(&[Key::Alt('b')], Action::BackwardWord)
+            .with(&[Key::Alt('f')], Action::ForwardWord)
+            .with(&[Key::AltBackspace], Action::BackwardKillWord)
+            .with(&[Key::Alt('d')], Action::KillWord)
+            .with(&[Key::Ctrl('k')], Action::KillLine)
+            .with(&[Key::Ctrl('y')], Action::Yank)
+            .with(&[Key::Ctrl('l')], Action::ClearScreen)
+            .with(&[Key::Ctrl('p')], Action::PreviousHistory)
+            .with(&[Key::Ctrl('n')], Action::NextHistory)
+    }
+
+    // insert mode of vi, which leaves most Ctrl keys alone
+    pub fn vi_insert() -> Self {
+        Keymap::basic().with(&[Key::Escape], Action::ViMovementMode)
+    }
+
+    pub fn vi_command() -> Self {
+        Keymap::new()
+            .with(&[Key::Enter, Key::Ctrl('j')], Action::AcceptLine)
+            .with(&[Key::Ctrl('c')], Action::Interrupt)
+            .with(&[Key::Ctrl('d')], Action::DeleteCharOrEof)
+            .with(&[Key::Ctrl('l')], Action::ClearScreen)
+            .with(&[Key::Up], Action::PreviousHistory)
+            .with(&[Key::Down], Action::NextHistory)
+            .with(&[Key::Ctrl('r')], Action::ReverseSearchHistory)
+    }
+}
+
+// the keymaps a new shell starts with, by name
+pub fn defaults() -> BTreeMap<String, Keymap> {
+    BTreeMap::from([
+        (EMACS.to_string(), Keymap::emacs()),
+        (VI_INSERT.to_string(), Keymap::vi_insert()),
+        (VI_COMMAND.to_string(), Keymap::vi_command()),
+    ])
+}
+
+const KEY_NAMES: &[(&str, Key)] = &[
+    ("Enter", Key::Enter),
+    ("Tab", Key::Tab),
+    ("S-Tab", Key::BackTab),
+    ("Backspace", Key::Backspace),
+    ("M-Backspace", Key::AltBackspace),
+    ("Delete", Key::Delete),
+    ("Esc", Key::Escape),
+    ("Left", Key::Left),
+    ("Right", Key::Right),
+    ("Up", Key::Up),
+    ("Down", Key::Down),
+    ("C-Left", Key::CtrlLeft),
+    ("C-Right", Key::CtrlRight),
+    ("Home", Key::Home),
+    ("End", Key::End),
+    ("PageUp", Key::PageUp),
+    ("PageDown", Key::PageDown),
+    ("Space", Key::Char(' ')),
+    ("C-Space", Key::Ctrl(' ')),
+];
+
+// Reads a key sequence written like `C-x C-e`: keys separated by spaces, each one a character,
+// `C-` or `M-` and a character for Ctrl and Alt, or a name like `Up`, `Tab` or `Esc`.
+pub fn parse_keys(spec: &str) -> Result<Vec<Key>, String> {
+    let keys: Vec<Key> = spec
+        .split_whitespace()
+        .map(|name| parse_key(name).ok_or_else(|| format!("{}: invalid key", name)))
+        .collect::<Result<_, _>>()?;
+    if keys.is_empty() {
+        return Err("empty key sequence".to_string());
+    }
+    Ok(keys)
+}
+
+fn parse_key(name: &str) -> Option<Key> {
+    if let Some((_, key)) = KEY_NAMES
+        .iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(name))
+    {
+        return Some(*key);
+    }
+
+    let single = |text: &str| {
+        let mut chars = text.chars();
+        chars.next().filter(|_| chars.next().is_none())
+    };
+    if let Some(rest) = name.strip_prefix("C-") {
+        // the terminal sends the same byte for Ctrl and either case, and for C-h the one
+        // Backspace sends on some terminals
+        return match single(rest)?.to_ascii_lowercase() {
+            'h' => Some(Key::Backspace),
+            'i' => Some(Key::Tab),
+            'm' => Some(Key::Enter),
+            c @ ('a'..='z' | '\\' | ']' | '^' | '_') => Some(Key::Ctrl(c)),
+            _ => None,
+        };
+    }
+    if let Some(rest) = name.strip_prefix("M-") {
+        return single(rest).filter(|c| c.is_ascii_graphic()).map(Key::Alt);
+    }
+    single(name).filter(|c| !c.is_control()).map(Key::Char)
+}
+
+pub fn format_keys(keys: &[Key]) -> String {
+    keys.iter()
+        .map(|&key| format_key(key))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn format_key(key: Key) -> String {
+    if let Some((name, _)) = KEY_NAMES.iter().find(|(_, known)| *known == key) {
+        return name.to_string();
+    }
+    match key {
+        Key::Char(c) => c.to_string(),
+        Key::Ctrl(c) => format!("C-{}", c),
+        Key::Alt(c) => format!("M-{}", c),
+        _ => "Unknown".to_string(),
+    }
+}
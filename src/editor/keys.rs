@@ -0,0 +1,127 @@
+use crate::editor::terminal;
+use std::io;
+use std::time::Duration;
+
+// how long to wait for the rest of an escape sequence before taking Esc as a key of its own
+const ESCAPE_TIMEOUT: Duration = Duration::from_millis(30);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Key {
+    Char(char),
+    // a letter pressed with Ctrl, e.g. `Ctrl('a')`
+    Ctrl(char),
+    // a character pressed with Alt, which terminals send as Esc followed by the character
+    Alt(char),
+    Enter,
+    Tab,
+    BackTab,
+    Backspace,
+    AltBackspace,
+    Delete,
+    Escape,
+    Left,
+    Right,
+    Up,
+    Down,
+    CtrlLeft,
+    CtrlRight,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Unknown,
+}
+
+// Blocks until the next key press on stdin, which must be in raw mode.
+pub fn read_key() -> io::Result<Key> {
+    let first = terminal::read_byte(None)?.ok_or(io::ErrorKind::UnexpectedEof)?;
+    let mut rest = std::iter::from_fn(|| terminal::read_byte(Some(ESCAPE_TIMEOUT)).ok()?);
+    Ok(decode(first, &mut rest))
+}
+
+// Turns the bytes a terminal sends for one key press into a `Key`. `rest` yields the bytes
+// after `first` that are already available.
+pub fn decode(first: u8, rest: &mut impl Iterator<Item = u8>) -> Key {
+    match first {
+        b'\r' => Key::Enter,
+        b'\t' => Key::Tab,
+        0x7f | 0x08 => Key::Backspace,
+        0x1b => decode_escape(rest),
+        0x01..=0x1a => Key::Ctrl((first - 1 + b'a') as char),
+        0x1c..=0x1f => Key::Ctrl((first + 0x40) as char),
+        0x00 => Key::Ctrl(' '),
+        0x20..=0x7e => Key::Char(first as char),
+        _ => decode_utf8(first, rest),
+    }
+}
+
+fn decode_escape(rest: &mut impl Iterator<Item = u8>) -> Key {
+    match rest.next() {
+        None => Key::Escape,
+        Some(b'[') => decode_csi(rest),
+        Some(b'O') => match rest.next() {
+            Some(b'A') => Key::Up,
+            Some(b'B') => Key::Down,
+            Some(b'C') => Key::Right,
+            Some(b'D') => Key::Left,
+            Some(b'H') => Key::Home,
+            Some(b'F') => Key::End,
+            _ => Key::Unknown,
+        },
+        Some(0x7f | 0x08) => Key::AltBackspace,
+        Some(byte @ 0x20..=0x7e) => Key::Alt(byte as char),
+        Some(_) => Key::Unknown,
+    }
+}
+
+// `Esc [ parameters final`, e.g. `\x1b[1;5C` for Ctrl-Right or `\x1b[3~` for Delete
+fn decode_csi(rest: &mut impl Iterator<Item = u8>) -> Key {
+    let mut parameters = String::new();
+    let last = loop {
+        match rest.next() {
+            Some(byte @ 0x40..=0x7e) => break byte,
+            Some(byte) => parameters.push(byte as char),
+            None => return Key::Unknown,
+        }
+    };
+
+    let control = parameters.ends_with(";5");
+    match last {
+        b'A' => Key::Up,
+        b'B' => Key::Down,
+        b'C' if control => Key::CtrlRight,
+        b'D' if control => Key::CtrlLeft,
+        b'C' => Key::Right,
+        b'D' => Key::Left,
+        b'H' => Key::Home,
+        b'F' => Key::End,
+        b'Z' => Key::BackTab,
+        b'~' => match parameters.as_str() {
+            "1" | "7" => Key::Home,
+            "4" | "8" => Key::End,
+            "3" => Key::Delete,
+            "5" => Key::PageUp,
+            "6" => Key::PageDown,
+            _ => Key::Unknown,
+        },
+        _ => Key::Unknown,
+    }
+}
+
+fn decode_utf8(first: u8, rest: &mut impl Iterator<Item = u8>) -> Key {
+    let length = match first {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => return Key::Unknown,
+    };
+    let mut bytes = vec![first];
+    bytes.extend(rest.take(length - 1));
+    match std::str::from_utf8(&bytes)
+        .ok()
+        .and_then(|s| s.chars().next())
+    {
+        Some(c) => Key::Char(c),
+        None => Key::Unknown,
+    }
+}
@@ -0,0 +1,627 @@
+use crate::banner::Banner;
+use crate::editor::buffer::LineBuffer;
+use crate::editor::completion::{self, CompletionContext};
+use crate::editor::highlight::{self, Theme};
+use crate::editor::keymap::{self, Action, Binding, Lookup};
+use crate::editor::keys::{self, Key};
+use crate::editor::search::{Search, SearchMode};
+use crate::editor::terminal::{self, RawMode};
+use crate::editor::vi::{self, Mode, Outcome, Vi};
+use crate::editor::width;
+use crate::history::History;
+use crate::interpreter::{signals, Interpreter};
+use crate::shell::Shell;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, Write};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReadLine {
+    Line(String),
+    // Ctrl-C threw the line away
+    Interrupted,
+    // Ctrl-D on an empty line, or stdin closed
+    Eof,
+    // a key bound with `bind -x` was pressed; the line being edited comes back the next time
+    Command(String),
+}
+
+// Reads lines in raw mode with cursor movement and editing. While a line is being edited the
+// prompt sits right above a banner line, unless there's none to show, and every change redraws
+// both.
+#[derive(Debug, Default)]
+pub struct LineEditor {
+    buffer: LineBuffer,
+    prompt: String,
+    banner: Option<Banner>,
+    // row of the terminal cursor, counted from the row the prompt starts on
+    cursor_row: usize,
+    // the text removed by the last kill command, for Ctrl-Y
+    killed: String,
+    // the history entry shown while browsing with Up/Down, and the line typed before that
+    history_index: Option<usize>,
+    draft: String,
+    // the Ctrl-R search in progress, the line it started from, and the matched byte offsets
+    // to highlight in the line
+    search: Option<Search>,
+    before_search: LineBuffer,
+    highlight: Vec<usize>,
+    // candidates shown below the line after an ambiguous Tab, until the next key
+    menu: Vec<String>,
+    // the line coloured by token, redone before every redraw
+    styled: String,
+    // keys read so far of a sequence that's bound to something
+    pending: Vec<Key>,
+    // the line to go on with after running a bound command
+    kept: Option<LineBuffer>,
+    // vi keys instead of emacs ones, with their mode shown in the banner, or before the prompt
+    // when there's no banner
+    vi_enabled: bool,
+    vi: Vi,
+}
+
+impl LineEditor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Shows `prompt` with `banner` on the line below and returns the line typed, without its
+    // line break. Falls back to plain reading when stdin or stdout isn't a terminal.
+    pub fn read_line(
+        &mut self,
+        prompt: &str,
+        banner: Option<&Banner>,
+        shell: &mut Shell,
+    ) -> io::Result<ReadLine> {
+        if !terminal::is_interactive() {
+            return read_plain_line(prompt);
+        }
+
+        self.buffer = self.kept.take().unwrap_or_default();
+        self.prompt = prompt.to_string();
+        self.banner = banner.cloned();
+        self.cursor_row = 0;
+        self.history_index = None;
+        self.search = None;
+        self.highlight.clear();
+        self.menu.clear();
+        self.pending.clear();
+        self.vi_enabled = shell.interpreter.options.vi;
+        self.vi.start_line(&self.buffer);
+
+        let _raw = RawMode::enable()?;
+        // a resize from before is already in the size this draws with
+        signals::take_resize();
+        self.restyle(&shell.interpreter);
+        self.render()?;
+        loop {
+            let key = match keys::read_key() {
+                Ok(key) => key,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {
+                    if signals::take_resize() {
+                        self.resized()?;
+                    }
+                    continue;
+                }
+                Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => {
+                    self.finish("")?;
+                    return Ok(ReadLine::Eof);
+                }
+                Err(error) => return Err(error),
+            };
+
+            if let Some(result) = self.handle(key, shell)? {
+                return Ok(result);
+            }
+            self.restyle(&shell.interpreter);
+            self.render()?;
+        }
+    }
+
+    // Looks the keys pressed so far up in the keymap and does what they're bound to. A
+    // printable character that isn't bound inserts itself.
+    fn handle(&mut self, key: Key, shell: &mut Shell) -> io::Result<Option<ReadLine>> {
+        if self.search.is_some() {
+            return self.handle_search(key, shell);
+        }
+        self.menu.clear();
+
+        let name = match (self.vi_enabled, self.vi.mode) {
+            (false, _) => keymap::EMACS,
+            (true, Mode::Insert) => keymap::VI_INSERT,
+            (true, Mode::Normal) => keymap::VI_COMMAND,
+        };
+        // a vi command being typed gets every key until it's complete
+        if name == keymap::VI_COMMAND && self.vi.is_pending() {
+            self.vi_command(key, shell);
+            return Ok(None);
+        }
+
+        self.pending.push(key);
+        let binding = match shell.keymaps.borrow().get(name) {
+            Some(keymap) => match keymap.lookup(&self.pending) {
+                Lookup::Prefix => return Ok(None),
+                Lookup::Bound(binding) => Some(binding.clone()),
+                Lookup::Unbound => None,
+            },
+            None => None,
+        };
+        let keys = std::mem::take(&mut self.pending);
+        match binding {
+            Some(Binding::Action(action)) => self.perform(action, shell),
+            Some(Binding::Command(command)) => {
+                self.finish("")?;
+                self.kept = Some(self.buffer.clone());
+                Ok(Some(ReadLine::Command(command)))
+            }
+            None => {
+                match keys[..] {
+                    [key] if name == keymap::VI_COMMAND => self.vi_command(key, shell),
+                    [Key::Char(c)] => {
+                        self.buffer.insert(c);
+                        self.vi.record(c);
+                    }
+                    _ => {}
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    // A key typed in vi normal mode, as part of a command like `d2w` or `fx`. Arrows and
+    // friends stand for the motions they'd be in vi.
+    fn vi_command(&mut self, key: Key, shell: &Shell) {
+        let c = match key {
+            Key::Char(c) => c,
+            Key::Left | Key::Backspace => 'h',
+            Key::Right => 'l',
+            Key::Home => '0',
+            Key::End => '$',
+            Key::Delete => 'x',
+            _ => {
+                self.vi.cancel();
+                return;
+            }
+        };
+        if let Outcome::History(step) = self.vi.feed(c, &mut self.buffer, &mut self.killed) {
+            for _ in 0..step.unsigned_abs() {
+                self.browse_history(&shell.history.borrow(), step.signum());
+            }
+            vi::clamp(&mut self.buffer);
+        }
+    }
+
+    fn perform(&mut self, action: Action, shell: &mut Shell) -> io::Result<Option<ReadLine>> {
+        let buffer = &mut self.buffer;
+        match action {
+            Action::AcceptLine => {
+                self.finish("")?;
+                return Ok(Some(ReadLine::Line(self.buffer.text().to_string())));
+            }
+            Action::Interrupt => {
+                self.finish("^C")?;
+                return Ok(Some(ReadLine::Interrupted));
+            }
+            Action::DeleteCharOrEof if buffer.is_empty() => {
+                self.finish("")?;
+                return Ok(Some(ReadLine::Eof));
+            }
+            Action::DeleteCharOrEof | Action::DeleteChar => {
+                buffer.delete_after();
+            }
+            Action::BackwardDeleteChar => {
+                buffer.delete_before();
+                self.vi.unrecord();
+            }
+            Action::BackwardChar => buffer.move_left(),
+            Action::ForwardChar => buffer.move_right(),
+            Action::BeginningOfLine => buffer.move_home(),
+            Action::EndOfLine => buffer.move_end(),
+            Action::BackwardWord => buffer.move_word_left(),
+            Action::ForwardWord => buffer.move_word_right(),
+            Action::UnixWordRubout => self.killed = buffer.delete_word_before(),
+            Action::BackwardKillWord => self.killed = buffer.delete_alnum_word_before(),
+            Action::KillWord => self.killed = buffer.delete_word_after(),
+            Action::UnixLineDiscard => self.killed = buffer.delete_to_start(),
+            Action::KillLine => self.killed = buffer.delete_to_end(),
+            Action::Yank => buffer.insert_str(&self.killed),
+            Action::ClearScreen => {
+                write_out("\x1b[H\x1b[2J")?;
+                self.cursor_row = 0;
+            }
+            Action::PreviousHistory => self.browse_history(&shell.history.borrow(), -1),
+            Action::NextHistory => self.browse_history(&shell.history.borrow(), 1),
+            Action::ReverseSearchHistory => {
+                self.before_search = buffer.clone();
+                let mut search = Search::new();
+                search.update(shell.history.borrow().entries());
+                self.search = Some(search);
+            }
+            Action::Complete => self.complete(shell),
+            Action::ViMovementMode => {
+                if self.vi_enabled {
+                    self.vi.enter_normal(buffer);
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    // Completes the word before the cursor: a command name or program path for the first word
+    // of a command, otherwise what the command's completer offers, or else a path. A unique
+    // match is put in whole, several matches as far as they agree, and when that adds nothing
+    // they're listed below the line.
+    fn complete(&mut self, shell: &mut Shell) {
+        let before_cursor = self.buffer.before_cursor();
+        let lookup = |name: &str| shell.interpreter.lookup_variable(name);
+        let mut words: Vec<(String, Option<char>)> = completion::command_words(before_cursor)
+            .into_iter()
+            .map(|(_, raw)| completion::unquote(raw, &lookup))
+            .collect();
+        let Some((word, quote)) = words.pop() else {
+            return;
+        };
+        let words: Vec<String> = words.into_iter().map(|(word, _)| word).collect();
+
+        // the candidates all continue `typed`, the part of the word they're matched against
+        let mut typed = word.as_str();
+        let mut candidates = Vec::new();
+        if words.is_empty() && !word.contains('/') {
+            candidates = shell.interpreter.command_names(&word);
+        } else if let Some((completer, words)) = shell.completer(&words) {
+            let context = CompletionContext {
+                line: self.buffer.text().to_string(),
+                cursor: self.buffer.cursor(),
+                words,
+                word: word.clone(),
+            };
+            candidates = completer.complete(&mut shell.interpreter, &context);
+            candidates.retain(|candidate| candidate.starts_with(&word));
+            candidates.sort();
+            candidates.dedup();
+        }
+        if candidates.is_empty() && (!words.is_empty() || word.contains('/')) {
+            typed = &word[word.rfind('/').map_or(0, |slash| slash + 1)..];
+            candidates = completion::path_candidates(&word, words.is_empty());
+        }
+
+        match candidates.as_slice() {
+            [] => {}
+            [only] => {
+                let mut insert = completion::quote(&only[typed.len()..], quote);
+                // a directory stays open for the next component
+                if !only.ends_with('/') {
+                    insert.extend(quote);
+                    insert.push(' ');
+                }
+                self.buffer.insert_str(&insert);
+            }
+            _ => {
+                let prefix = completion::common_prefix(&candidates);
+                if prefix.len() > typed.len() {
+                    let insert = completion::quote(&prefix[typed.len()..], quote);
+                    self.buffer.insert_str(&insert);
+                } else {
+                    self.menu = candidates;
+                }
+            }
+        }
+    }
+
+    // Keys while searching with Ctrl-R. Typing refines the query, Ctrl-R and Ctrl-S step to
+    // older and newer matches, Ctrl-T switches to fuzzy matching and Ctrl-O to this directory
+    // only. Enter runs the match, Ctrl-G gives up, and any other key keeps the match to edit.
+    fn handle_search(&mut self, key: Key, shell: &mut Shell) -> io::Result<Option<ReadLine>> {
+        let history = shell.history.clone();
+        let history = history.borrow();
+        let Some(search) = &mut self.search else {
+            return Ok(None);
+        };
+        match key {
+            Key::Char(c) => {
+                search.query.push(c);
+                search.update(history.entries());
+            }
+            Key::Backspace => {
+                search.query.pop();
+                search.update(history.entries());
+            }
+            Key::Ctrl('r') | Key::Up => search.older(),
+            Key::Ctrl('s') | Key::Down => search.newer(),
+            Key::Ctrl('t') => {
+                search.toggle_mode();
+                search.update(history.entries());
+            }
+            Key::Ctrl('o') => {
+                search.directory = match search.directory {
+                    Some(_) => None,
+                    None => std::env::current_dir()
+                        .ok()
+                        .map(|cwd| cwd.display().to_string()),
+                };
+                search.update(history.entries());
+            }
+            Key::Ctrl('g') | Key::Ctrl('c') => {
+                self.search = None;
+                self.highlight.clear();
+                self.buffer = self.before_search.clone();
+                return match key {
+                    Key::Ctrl('c') => self.perform(Action::Interrupt, shell),
+                    _ => Ok(None),
+                };
+            }
+            Key::Escape | Key::Tab => {
+                self.stop_search();
+                return Ok(None);
+            }
+            _ => {
+                self.stop_search();
+                drop(history);
+                return self.handle(key, shell);
+            }
+        }
+
+        // the line shows the selected match, or the last one found while nothing matches
+        self.highlight.clear();
+        if let Some((index, positions)) = search.current() {
+            if !search.query.is_empty() {
+                self.buffer = LineBuffer::new(&history.entries()[index].command);
+                self.highlight = positions.to_vec();
+            }
+        }
+        Ok(None)
+    }
+
+    fn stop_search(&mut self) {
+        self.search = None;
+        self.highlight.clear();
+        self.history_index = None;
+    }
+
+    // Steps through older (-1) or newer (1) history entries. Going past the newest one brings
+    // back the line that was being typed.
+    fn browse_history(&mut self, history: &History, step: isize) {
+        let entries = history.entries();
+        let current = self.history_index.unwrap_or(entries.len());
+        let Some(next) = current.checked_add_signed(step) else {
+            return;
+        };
+        if next > entries.len() {
+            return;
+        }
+
+        if self.history_index.is_none() {
+            self.draft = self.buffer.text().to_string();
+        }
+        if next == entries.len() {
+            self.history_index = None;
+            self.buffer = LineBuffer::new(&self.draft);
+        } else {
+            self.history_index = Some(next);
+            self.buffer = LineBuffer::new(&entries[next].command);
+        }
+    }
+
+    // Colours the line with the theme in $SOSH_COLORS, marking command names by whether they
+    // resolve.
+    fn restyle(&mut self, interpreter: &Interpreter) {
+        let theme = interpreter
+            .lookup_variable("SOSH_COLORS")
+            .map_or_else(Theme::default, |spec| Theme::parse(&spec));
+        let lookup = |name: &str| interpreter.lookup_variable(name);
+        let is_command =
+            |raw: &str| interpreter.command_exists(&completion::unquote(raw, &lookup).0);
+        self.styled = highlight::highlight(self.buffer.text(), &theme, &is_command);
+    }
+
+    // Redraws the prompt, the line and the banner below them, then puts the cursor back.
+    fn render(&mut self) -> io::Result<()> {
+        let (columns, rows) = terminal::size();
+        let prompt = self.editing_prompt();
+        let mut out = self.redraw_line(&prompt);
+
+        let prompt_end = width::advance(&prompt, columns, (0, 0));
+        let end = width::advance(self.buffer.text(), columns, prompt_end);
+        let mut cursor = width::advance(self.buffer.before_cursor(), columns, prompt_end);
+        // a full last row only wraps once something follows, so the banner needs a new row
+        let mut end_row = end.0;
+        if end.1 == columns {
+            out.push_str("\r\n");
+            end_row += 1;
+        }
+        if cursor.1 == columns {
+            cursor = (cursor.0 + 1, 0);
+        }
+
+        // the menu goes between the line and the banner, cut short when the screen is too small
+        let mut menu = completion::menu_rows(&self.menu, columns);
+        let room = rows.saturating_sub(end_row + 2).max(1);
+        if menu.len() > room {
+            let hidden = menu.len() - room + 1;
+            menu.truncate(room - 1);
+            menu.push(format!("… {} more rows", hidden));
+        }
+        for row in &menu {
+            out.push_str("\r\n");
+            out.push_str(width::truncate(row, columns.saturating_sub(1)).0);
+        }
+        let mut last_row = end_row + menu.len();
+
+        // a search takes over the banner, with the cursor after its query
+        match (&self.search, &self.banner) {
+            (Some(search), _) => {
+                let (status, column) = search_status(search, columns);
+                out.push_str("\r\n");
+                out.push_str(&status);
+                last_row += 1;
+                cursor = (last_row, column);
+            }
+            // in vi mode the banner starts with the mode
+            (None, Some(banner)) if self.vi_enabled => {
+                let tag = self.mode_tag();
+                let room = columns.saturating_sub(width::display_width(tag));
+                out.push_str("\r\n");
+                out.push_str(tag);
+                out.push_str(&banner.layout(room));
+                last_row += 1;
+            }
+            (None, Some(banner)) => {
+                out.push_str("\r\n");
+                out.push_str(&banner.layout(columns));
+                last_row += 1;
+            }
+            (None, None) => {}
+        }
+
+        if last_row > cursor.0 {
+            let _ = write!(out, "\x1b[{}A", last_row - cursor.0);
+        }
+        out.push('\r');
+        if cursor.1 > 0 {
+            let _ = write!(out, "\x1b[{}C", cursor.1);
+        }
+        self.cursor_row = cursor.0;
+        write_out(&out)
+    }
+
+    // Redraws everything for a terminal that changed size. Terminals rewrap what's on screen
+    // to the new width, so the cursor is now where that width puts it, and the menu laid out
+    // for the old one goes.
+    fn resized(&mut self) -> io::Result<()> {
+        let columns = terminal::columns();
+        self.menu.clear();
+        let prompt_end = width::advance(&self.editing_prompt(), columns, (0, 0));
+        // a search keeps the cursor on the banner row, right under the line
+        let (row, column) = match self.search {
+            Some(_) => width::advance(self.buffer.text(), columns, prompt_end),
+            None => width::advance(self.buffer.before_cursor(), columns, prompt_end),
+        };
+        // a full row puts the cursor on the next one, as in `render`
+        self.cursor_row = row + usize::from(column == columns) + usize::from(self.search.is_some());
+        self.render()
+    }
+
+    // The vi mode, shown in the banner or, without one, before the prompt.
+    fn mode_tag(&self) -> &'static str {
+        match self.vi.mode {
+            Mode::Insert => "\x1b[1;30;42m INSERT \x1b[0m",
+            Mode::Normal => "\x1b[1;30;44m NORMAL \x1b[0m",
+        }
+    }
+
+    // the prompt drawn while the line is being edited
+    fn editing_prompt(&self) -> String {
+        if self.vi_enabled && self.banner.is_none() {
+            format!("{} {}", self.mode_tag(), self.prompt)
+        } else {
+            self.prompt.clone()
+        }
+    }
+
+    // moves back to where the prompt starts and prints it with the line, clearing the rest
+    fn redraw_line(&self, prompt: &str) -> String {
+        let mut out = String::new();
+        if self.cursor_row > 0 {
+            let _ = write!(out, "\x1b[{}A", self.cursor_row);
+        }
+        out.push_str("\r\x1b[J");
+        out.push_str(prompt);
+        // search matches are the only highlighting while searching
+        if self.highlight.is_empty() && self.search.is_none() {
+            out.push_str(&self.styled);
+            return out;
+        }
+        let text = self.buffer.text();
+        let mut last = 0;
+        for &offset in &self.highlight {
+            let end = offset + text[offset..].chars().next().map_or(0, char::len_utf8);
+            out.push_str(&text[last..offset]);
+            let _ = write!(out, "\x1b[1;4m{}\x1b[22;24m", &text[offset..end]);
+            last = end;
+        }
+        out.push_str(&text[last..]);
+        out
+    }
+
+    // Leaves the line as typed, followed by `marker`, and drops the banner and the vi mode so
+    // output starts on the next row.
+    fn finish(&mut self, marker: &str) -> io::Result<()> {
+        let mut out = self.redraw_line(&self.prompt);
+        out.push_str(marker);
+        out.push_str("\r\n");
+        self.cursor_row = 0;
+        write_out(&out)
+    }
+}
+
+// The banner row during a search, e.g. ` search (fuzzy): grp    2/14 `, cut to fit a row, and
+// the column right after the query.
+fn search_status(search: &Search, columns: usize) -> (String, usize) {
+    let mut label = " search".to_string();
+    if search.mode == SearchMode::Fuzzy {
+        label.push_str(" (fuzzy)");
+    }
+    if search.directory.is_some() {
+        label.push_str(" (here)");
+    }
+    let left = format!("{}: {}", label, search.query);
+    let right = if search.is_empty() {
+        "no match ".to_string()
+    } else {
+        format!("{}/{} ", search.selected() + 1, search.len())
+    };
+
+    // the last column stays empty so the row never wraps
+    let room = columns.saturating_sub(1);
+    let (left, used) = width::truncate(&left, room);
+    let mut text = left.to_string();
+    let column = used;
+    let right_width = width::display_width(&right);
+    if used + 1 + right_width <= room {
+        text.push_str(&" ".repeat(room - used - right_width));
+        text.push_str(&right);
+    }
+    (format!("\x1b[1m\x1b[37m\x1b[100m{}\x1b[0m", text), column)
+}
+
+fn write_out(text: &str) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(text.as_bytes())?;
+    stdout.flush()
+}
+
+// Prints `prompt` and reads up to the next line break. Unlike `Stdin::read_line` it gives up
+// when Ctrl-C interrupts the read instead of retrying.
+fn read_plain_line(prompt: &str) -> io::Result<ReadLine> {
+    write_out(prompt)?;
+
+    let mut stdin = io::stdin().lock();
+    let mut line = Vec::new();
+    loop {
+        let available = match stdin.fill_buf() {
+            Ok(available) => available,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => {
+                return Ok(ReadLine::Interrupted);
+            }
+            Err(error) => return Err(error),
+        };
+        if available.is_empty() {
+            if line.is_empty() {
+                return Ok(ReadLine::Eof);
+            }
+            break;
+        }
+        match available.iter().position(|&byte| byte == b'\n') {
+            Some(end) => {
+                line.extend_from_slice(&available[..end]);
+                stdin.consume(end + 1);
+                break;
+            }
+            None => {
+                let length = available.len();
+                line.extend_from_slice(available);
+                stdin.consume(length);
+            }
+        }
+    }
+    Ok(ReadLine::Line(String::from_utf8_lossy(&line).into_owned()))
+}
@@ -0,0 +1,16 @@
+pub mod buffer;
+pub mod completion;
+pub mod highlight;
+pub mod keymap;
+pub mod keys;
+pub mod line_editor;
+pub mod search;
+pub mod terminal;
+pub mod vi;
+pub mod width;
+
+pub use buffer::LineBuffer;
+pub use keymap::{Action, Binding, Keymap};
+pub use keys::Key;
+pub use line_editor::{LineEditor, ReadLine};
+pub use search::{Search, SearchMode};
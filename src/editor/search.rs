@@ -0,0 +1,132 @@
+use crate::history::Entry;
+use std::collections::HashSet;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SearchMode {
+    // the query appears as is somewhere in the command
+    #[default]
+    Substring,
+    // the characters of the query appear in order, with anything in between
+    Fuzzy,
+}
+
+// State of an incremental reverse search through the history: the query, how it matches, and
+// the entries it currently finds, newest first.
+#[derive(Clone, Debug, Default)]
+pub struct Search {
+    pub query: String,
+    pub mode: SearchMode,
+    // only entries that ran in this directory
+    pub directory: Option<String>,
+    // history indices with the byte offsets of the characters the query matched
+    matches: Vec<(usize, Vec<usize>)>,
+    selected: usize,
+}
+
+impl Search {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Looks for the query in `entries` again, starting over from the newest match. A command
+    // that was run several times is only found once.
+    pub fn update(&mut self, entries: &[Entry]) {
+        let mut seen = HashSet::new();
+        self.matches = entries
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, entry)| {
+                self.directory
+                    .as_ref()
+                    .is_none_or(|directory| entry.cwd == *directory)
+            })
+            .filter(|(_, entry)| seen.insert(entry.command.as_str()))
+            .filter_map(|(index, entry)| {
+                let positions = find_match(&entry.command, &self.query, self.mode)?;
+                Some((index, positions))
+            })
+            .collect();
+        self.selected = 0;
+    }
+
+    // the selected match as a history index and the matched byte offsets
+    pub fn current(&self) -> Option<(usize, &[usize])> {
+        let (index, positions) = self.matches.get(self.selected)?;
+        Some((*index, positions))
+    }
+
+    // position of the selected match among all of them, counting from 0
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn len(&self) -> usize {
+        self.matches.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.matches.is_empty()
+    }
+
+    pub fn older(&mut self) {
+        if self.selected + 1 < self.matches.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn newer(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn toggle_mode(&mut self) {
+        self.mode = match self.mode {
+            SearchMode::Substring => SearchMode::Fuzzy,
+            SearchMode::Fuzzy => SearchMode::Substring,
+        };
+    }
+}
+
+// Matches `query` against `command`, returning the byte offsets of the matched characters.
+// The match ignores case unless the query has an upper case letter in it.
+pub fn find_match(command: &str, query: &str, mode: SearchMode) -> Option<Vec<usize>> {
+    let ignore_case = !query.chars().any(char::is_uppercase);
+    let same = |a: char, b: char| {
+        if ignore_case {
+            a.to_lowercase().eq(b.to_lowercase())
+        } else {
+            a == b
+        }
+    };
+    let query: Vec<char> = query.chars().collect();
+    let chars: Vec<(usize, char)> = command.char_indices().collect();
+
+    match mode {
+        SearchMode::Substring => {
+            if query.is_empty() {
+                return Some(Vec::new());
+            }
+            // the rightmost occurrence, like other shells' reverse search
+            chars
+                .windows(query.len())
+                .rev()
+                .find(|window| window.iter().zip(&query).all(|(&(_, a), &b)| same(a, b)))
+                .map(|window| window.iter().map(|&(offset, _)| offset).collect())
+        }
+        SearchMode::Fuzzy => {
+            let mut positions = Vec::new();
+            let mut wanted = query.iter().peekable();
+            for &(offset, c) in &chars {
+                match wanted.peek() {
+                    Some(&&next) if same(c, next) => {
+                        positions.push(offset);
+                        wanted.next();
+                    }
+                    Some(_) => {}
+                    None => break,
+                }
+            }
+            wanted.peek().is_none().then_some(positions)
+        }
+    }
+}
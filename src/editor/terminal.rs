@@ -0,0 +1,77 @@
+use std::io::{self, IsTerminal};
+use std::time::Duration;
+
+// Raw mode for as long as the value lives: no line buffering or echo, and Ctrl-C, Ctrl-Z and
+// friends arrive as key presses instead of signals.
+pub struct RawMode {
+    original: libc::termios,
+}
+
+impl RawMode {
+    pub fn enable() -> io::Result<RawMode> {
+        // SAFETY: termios is plain data that tcgetattr fills in
+        let mut original: libc::termios = unsafe { std::mem::zeroed() };
+        // SAFETY: valid descriptor and out pointer
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut raw = original;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG | libc::IEXTEN);
+        raw.c_iflag &= !(libc::IXON | libc::ICRNL | libc::INLCR);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        // SAFETY: see above
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &raw) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(RawMode { original })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        // SAFETY: restores the settings read in `enable`
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &self.original) };
+    }
+}
+
+// whether the shell talks to a person, so prompts and line editing make sense
+pub fn is_interactive() -> bool {
+    io::stdin().is_terminal() && io::stdout().is_terminal()
+}
+
+pub fn columns() -> usize {
+    size().0
+}
+
+// the terminal's columns and rows
+pub fn size() -> (usize, usize) {
+    term_size::dimensions().map_or((80, 24), |(width, height)| (width.max(1), height.max(1)))
+}
+
+// Reads one byte from stdin, giving up with `None` once `timeout` passes. A signal arriving
+// while waiting yields `ErrorKind::Interrupted`, even one set to restart system calls: the
+// waiting is done by poll, which never restarts.
+pub fn read_byte(timeout: Option<Duration>) -> io::Result<Option<u8>> {
+    let mut poll = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let timeout = timeout.map_or(-1, |timeout| timeout.as_millis() as i32);
+    // SAFETY: one valid pollfd
+    match unsafe { libc::poll(&mut poll, 1, timeout) } {
+        -1 => return Err(io::Error::last_os_error()),
+        0 => return Ok(None),
+        _ => {}
+    }
+
+    let mut byte = 0u8;
+    // SAFETY: reads at most one byte into `byte`
+    match unsafe { libc::read(libc::STDIN_FILENO, (&mut byte as *mut u8).cast(), 1) } {
+        -1 => Err(io::Error::last_os_error()),
+        0 => Ok(None),
+        _ => Ok(Some(byte)),
+    }
+}
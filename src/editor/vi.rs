@@ -0,0 +1,616 @@
+use crate::editor::buffer::LineBuffer;
+use crate::editor::width::is_combining;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Mode {
+    #[default]
+    Insert,
+    Normal,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Motion {
+    Left,
+    Right,
+    // `w`, `b` and `e`, or `W`, `B` and `E` when `big`
+    WordForward {
+        big: bool,
+    },
+    WordBackward {
+        big: bool,
+    },
+    WordEnd {
+        big: bool,
+    },
+    // `0`, `^` and `$`
+    Start,
+    FirstNonBlank,
+    End,
+    // `f`, `t`, `F` and `T`
+    Find {
+        target: char,
+        forward: bool,
+        till: bool,
+    },
+    // `;`, or `,` when `reverse`
+    RepeatFind {
+        reverse: bool,
+    },
+    // the whole line, for `dd`, `cc` and `yy`
+    Line,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operator {
+    Delete,
+    Change,
+    Yank,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    Move(Motion),
+    Operate(Operator, Motion),
+    // `i` and `a`, and `I` and `A` that go where their motion does first
+    Insert,
+    Append,
+    InsertAt(Motion),
+    // `r`
+    Replace(char),
+    // `p`, or `P` when `before`
+    Put { before: bool },
+    Undo,
+    // `.`
+    Repeat,
+    // `k` and `j`, as older (-1) and newer (1) history entries
+    History(isize),
+}
+
+// The largest count a command takes, so `99999999999p` can't make a line nobody could edit
+// or keep the shell busy for good.
+pub const MAX_COUNT: usize = 1000;
+
+// What typing a key in normal mode came to.
+#[derive(Debug, PartialEq)]
+pub enum Parse {
+    // a count, operator or `f` still waiting for the rest
+    Incomplete,
+    Invalid,
+    Complete(Option<usize>, Command),
+}
+
+// Reads a normal mode command, `[count] command` where a command can be an operator with its
+// own count and a motion, e.g. `2d3w`.
+pub fn parse(keys: &[char]) -> Parse {
+    let mut keys = keys.iter().copied().peekable();
+    let mut count = read_count(&mut keys);
+    let Some(key) = keys.next() else {
+        return Parse::Incomplete;
+    };
+
+    let command = match key {
+        'd' | 'c' | 'y' => {
+            let operator = match key {
+                'd' => Operator::Delete,
+                'c' => Operator::Change,
+                _ => Operator::Yank,
+            };
+            if let Some(motion_count) = read_count(&mut keys) {
+                count = Some(
+                    count
+                        .unwrap_or(1)
+                        .saturating_mul(motion_count)
+                        .min(MAX_COUNT),
+                );
+            }
+            let Some(next) = keys.next() else {
+                return Parse::Incomplete;
+            };
+            let motion = if next == key {
+                Motion::Line
+            } else {
+                match read_motion(next, &mut keys) {
+                    Parse::Complete(_, Command::Move(motion)) => motion,
+                    other => return other,
+                }
+            };
+            Command::Operate(operator, motion)
+        }
+        'x' => Command::Operate(Operator::Delete, Motion::Right),
+        'X' => Command::Operate(Operator::Delete, Motion::Left),
+        'D' => Command::Operate(Operator::Delete, Motion::End),
+        'C' => Command::Operate(Operator::Change, Motion::End),
+        's' => Command::Operate(Operator::Change, Motion::Right),
+        'S' => Command::Operate(Operator::Change, Motion::Line),
+        'Y' => Command::Operate(Operator::Yank, Motion::Line),
+        'i' => Command::Insert,
+        'a' => Command::Append,
+        'I' => Command::InsertAt(Motion::FirstNonBlank),
+        'A' => Command::InsertAt(Motion::End),
+        'r' => match keys.next() {
+            Some(c) => Command::Replace(c),
+            None => return Parse::Incomplete,
+        },
+        'p' => Command::Put { before: false },
+        'P' => Command::Put { before: true },
+        'u' => Command::Undo,
+        '.' => Command::Repeat,
+        'k' => Command::History(-1),
+        'j' => Command::History(1),
+        _ => match read_motion(key, &mut keys) {
+            Parse::Complete(_, command) => command,
+            other => return other,
+        },
+    };
+    if keys.next().is_some() {
+        return Parse::Invalid;
+    }
+    Parse::Complete(count, command)
+}
+
+// digits not starting with 0, which is a motion of its own, up to `MAX_COUNT`
+fn read_count(keys: &mut std::iter::Peekable<impl Iterator<Item = char>>) -> Option<usize> {
+    let mut count: Option<usize> = None;
+    while let Some(digit) = keys.next_if(|c| c.is_ascii_digit() && (count.is_some() || *c != '0')) {
+        let value = digit.to_digit(10).unwrap_or(0) as usize;
+        count = Some(count.unwrap_or(0).saturating_mul(10).saturating_add(value));
+    }
+    count.map(|count| count.min(MAX_COUNT))
+}
+
+fn read_motion(key: char, keys: &mut impl Iterator<Item = char>) -> Parse {
+    let motion = match key {
+        'h' => Motion::Left,
+        'l' | ' ' => Motion::Right,
+        'w' | 'W' => Motion::WordForward { big: key == 'W' },
+        'b' | 'B' => Motion::WordBackward { big: key == 'B' },
+        'e' | 'E' => Motion::WordEnd { big: key == 'E' },
+        '0' => Motion::Start,
+        '^' => Motion::FirstNonBlank,
+        '$' => Motion::End,
+        'f' | 't' | 'F' | 'T' => match keys.next() {
+            Some(target) => Motion::Find {
+                target,
+                forward: key.is_lowercase(),
+                till: matches!(key, 't' | 'T'),
+            },
+            None => return Parse::Incomplete,
+        },
+        ';' => Motion::RepeatFind { reverse: false },
+        ',' => Motion::RepeatFind { reverse: true },
+        _ => return Parse::Invalid,
+    };
+    Parse::Complete(None, Command::Move(motion))
+}
+
+// what the editor has to do after a normal mode command
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Done,
+    History(isize),
+}
+
+// Vi editing state of the line being edited: the mode, the normal mode command being typed,
+// and what `;`, `.` and `u` go back to.
+#[derive(Clone, Debug, Default)]
+pub struct Vi {
+    pub mode: Mode,
+    keys: Vec<char>,
+    last_find: Option<(char, bool, bool)>,
+    // the last change with what was typed in the insert mode it started, for `.`
+    last_change: Option<(usize, Command, String)>,
+    // the change that started the current insert mode and the text typed since
+    recording: Option<(usize, Command)>,
+    inserted: String,
+    undo: Vec<LineBuffer>,
+}
+
+impl Vi {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Starts editing `buffer` in insert mode, with nothing to undo but what gets typed.
+    pub fn start_line(&mut self, buffer: &LineBuffer) {
+        self.mode = Mode::Insert;
+        self.keys.clear();
+        self.recording = None;
+        self.undo = vec![buffer.clone()];
+    }
+
+    pub fn is_pending(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    // forgets a half typed command
+    pub fn cancel(&mut self) {
+        self.keys.clear();
+    }
+
+    // Keeps track of what's typed in insert mode, so `.` can type it again.
+    pub fn record(&mut self, c: char) {
+        if self.recording.is_some() {
+            self.inserted.push(c);
+        }
+    }
+
+    pub fn unrecord(&mut self) {
+        if self.recording.is_some() {
+            self.inserted.pop();
+        }
+    }
+
+    // Esc in insert mode: the cursor steps back onto the last character typed, like in vi.
+    pub fn enter_normal(&mut self, buffer: &mut LineBuffer) {
+        if self.mode == Mode::Normal {
+            return;
+        }
+        if let Some((count, command)) = self.recording.take() {
+            self.last_change = Some((count, command, std::mem::take(&mut self.inserted)));
+        }
+        self.mode = Mode::Normal;
+        buffer.move_left();
+    }
+
+    // Adds a key to the command being typed in normal mode and runs the command once complete.
+    // Deleted and yanked text goes to `register`, which `p` puts back.
+    pub fn feed(&mut self, key: char, buffer: &mut LineBuffer, register: &mut String) -> Outcome {
+        self.keys.push(key);
+        match parse(&self.keys) {
+            Parse::Incomplete => Outcome::Done,
+            Parse::Invalid => {
+                self.keys.clear();
+                Outcome::Done
+            }
+            Parse::Complete(count, command) => {
+                self.keys.clear();
+                let outcome = match command {
+                    Command::Repeat => {
+                        self.repeat(count, buffer, register);
+                        Outcome::Done
+                    }
+                    _ => self.execute(count.unwrap_or(1), command, buffer, register),
+                };
+                if self.mode == Mode::Normal {
+                    clamp(buffer);
+                }
+                outcome
+            }
+        }
+    }
+
+    fn execute(
+        &mut self,
+        count: usize,
+        command: Command,
+        buffer: &mut LineBuffer,
+        register: &mut String,
+    ) -> Outcome {
+        match command {
+            Command::Move(motion) => {
+                if let Some((target, _)) = self.target(motion, count, buffer) {
+                    buffer.set_cursor(target);
+                }
+            }
+            Command::Operate(operator, motion) => {
+                let motion = match (operator, motion) {
+                    // `cw` on a word changes just that word, like `ce`
+                    (Operator::Change, Motion::WordForward { big })
+                        if buffer.text()[buffer.cursor()..]
+                            .starts_with(|c: char| !c.is_whitespace()) =>
+                    {
+                        Motion::WordEnd { big }
+                    }
+                    _ => motion,
+                };
+                let Some((start, end)) = self.range(motion, count, buffer) else {
+                    return Outcome::Done;
+                };
+                *register = buffer.text()[start..end].to_string();
+                match operator {
+                    Operator::Yank if motion != Motion::Line => buffer.set_cursor(start),
+                    Operator::Yank => {}
+                    Operator::Delete => {
+                        self.snapshot(buffer);
+                        buffer.cut(start, end);
+                        self.last_change = Some((count, command, String::new()));
+                    }
+                    Operator::Change => {
+                        self.snapshot(buffer);
+                        buffer.cut(start, end);
+                        self.insert(count, command);
+                    }
+                }
+            }
+            Command::Insert | Command::Append | Command::InsertAt(_) => {
+                self.snapshot(buffer);
+                match command {
+                    Command::Append => buffer.move_right(),
+                    Command::InsertAt(motion) => {
+                        if let Some((target, _)) = self.target(motion, 1, buffer) {
+                            buffer.set_cursor(target);
+                        }
+                    }
+                    _ => {}
+                }
+                self.insert(count, command);
+            }
+            Command::Replace(c) => {
+                let start = buffer.cursor();
+                let mut end = start;
+                for _ in 0..count {
+                    if end == buffer.text().len() {
+                        return Outcome::Done;
+                    }
+                    end = buffer.next(end);
+                }
+                self.snapshot(buffer);
+                buffer.cut(start, end);
+                buffer.insert_str(&c.to_string().repeat(count));
+                buffer.move_left();
+                self.last_change = Some((count, command, String::new()));
+            }
+            Command::Put { before } => {
+                if register.is_empty() {
+                    return Outcome::Done;
+                }
+                self.snapshot(buffer);
+                if !before {
+                    buffer.move_right();
+                }
+                buffer.insert_str(&register.repeat(count));
+                buffer.move_left();
+                self.last_change = Some((count, command, String::new()));
+            }
+            Command::Undo => {
+                for _ in 0..count {
+                    match self.undo.pop() {
+                        Some(previous) => *buffer = previous,
+                        None => break,
+                    }
+                }
+            }
+            Command::Repeat => {}
+            Command::History(step) => return Outcome::History(step * count as isize),
+        }
+        Outcome::Done
+    }
+
+    // `.` makes the last change again, typing what was typed after it, `count` times if given
+    // or as many as the change itself.
+    fn repeat(&mut self, count: Option<usize>, buffer: &mut LineBuffer, register: &mut String) {
+        let Some((last_count, last, text)) = self.last_change.clone() else {
+            return;
+        };
+        let count = count.unwrap_or(last_count);
+        self.execute(count, last, buffer, register);
+        if self.mode == Mode::Insert {
+            buffer.insert_str(&text);
+            self.recording = None;
+            self.mode = Mode::Normal;
+            buffer.move_left();
+        }
+        self.last_change = Some((count, last, text));
+    }
+
+    fn insert(&mut self, count: usize, command: Command) {
+        self.mode = Mode::Insert;
+        self.recording = Some((count, command));
+        self.inserted.clear();
+    }
+
+    fn snapshot(&mut self, buffer: &LineBuffer) {
+        self.undo.push(buffer.clone());
+    }
+
+    // The byte range an operator with `motion` works on. Motions onto a character, like `e`
+    // and `f`, include it.
+    fn range(
+        &mut self,
+        motion: Motion,
+        count: usize,
+        buffer: &LineBuffer,
+    ) -> Option<(usize, usize)> {
+        if motion == Motion::Line {
+            return Some((0, buffer.text().len()));
+        }
+        let cursor = buffer.cursor();
+        let (target, inclusive) = self.target(motion, count, buffer)?;
+        if target < cursor {
+            return Some((target, cursor));
+        }
+        let end = if inclusive && target < buffer.text().len() {
+            buffer.next(target)
+        } else {
+            target
+        };
+        Some((cursor, end))
+    }
+
+    // Where `motion` repeated `count` times takes the cursor, and whether an operator
+    // includes the character there.
+    fn target(
+        &mut self,
+        motion: Motion,
+        count: usize,
+        buffer: &LineBuffer,
+    ) -> Option<(usize, bool)> {
+        let text = buffer.text();
+        let cells = Cells::new(text);
+        let mut index = cells.index(buffer.cursor());
+        let mut inclusive = false;
+        match motion {
+            Motion::Left => index = index.saturating_sub(count),
+            Motion::Right => index = (index + count).min(cells.len()),
+            Motion::WordForward { big } => {
+                for _ in 0..count {
+                    index = cells.word_forward(index, big);
+                }
+            }
+            Motion::WordBackward { big } => {
+                for _ in 0..count {
+                    index = cells.word_backward(index, big);
+                }
+            }
+            Motion::WordEnd { big } => {
+                for _ in 0..count {
+                    index = cells.word_end(index, big);
+                }
+                inclusive = true;
+            }
+            Motion::Start => index = 0,
+            Motion::FirstNonBlank => index = cells.first_non_blank(),
+            Motion::End => index = cells.len(),
+            Motion::Find {
+                target,
+                forward,
+                till,
+            } => {
+                self.last_find = Some((target, forward, till));
+                index = cells.find(index, count, target, forward, till)?;
+                inclusive = forward;
+            }
+            Motion::RepeatFind { reverse } => {
+                let (target, forward, till) = self.last_find?;
+                let forward = forward != reverse;
+                index = cells.find(index, count, target, forward, till)?;
+                inclusive = forward;
+            }
+            Motion::Line => {}
+        }
+        Some((cells.offset(index), inclusive))
+    }
+}
+
+// In normal mode the cursor sits on a character, never after the last one.
+pub fn clamp(buffer: &mut LineBuffer) {
+    let end = buffer.text().len();
+    if end > 0 && buffer.cursor() == end {
+        buffer.set_cursor(buffer.previous(end));
+    }
+}
+
+// what a word motion sees: blanks, word characters and everything else
+fn class(c: char, big: bool) -> u8 {
+    if c.is_whitespace() {
+        0
+    } else if big || c.is_alphanumeric() || c == '_' {
+        1
+    } else {
+        2
+    }
+}
+
+// The characters of a line as the cursor steps over them, combining marks going with the
+// character before them.
+struct Cells<'a> {
+    text: &'a str,
+    cells: Vec<(usize, char)>,
+}
+
+impl<'a> Cells<'a> {
+    fn new(text: &'a str) -> Self {
+        let cells = text
+            .char_indices()
+            .filter(|&(offset, c)| offset == 0 || !is_combining(c))
+            .collect();
+        Cells { text, cells }
+    }
+
+    fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    fn index(&self, offset: usize) -> usize {
+        self.cells.partition_point(|&(start, _)| start < offset)
+    }
+
+    fn offset(&self, index: usize) -> usize {
+        self.cells
+            .get(index)
+            .map_or(self.text.len(), |&(offset, _)| offset)
+    }
+
+    fn class(&self, index: usize, big: bool) -> u8 {
+        class(self.cells[index].1, big)
+    }
+
+    fn word_forward(&self, mut index: usize, big: bool) -> usize {
+        let len = self.len();
+        if index >= len {
+            return len;
+        }
+        let start = self.class(index, big);
+        if start != 0 {
+            while index < len && self.class(index, big) == start {
+                index += 1;
+            }
+        }
+        while index < len && self.class(index, big) == 0 {
+            index += 1;
+        }
+        index
+    }
+
+    fn word_backward(&self, mut index: usize, big: bool) -> usize {
+        if index == 0 {
+            return 0;
+        }
+        index -= 1;
+        while index > 0 && self.class(index, big) == 0 {
+            index -= 1;
+        }
+        let class = self.class(index, big);
+        while index > 0 && self.class(index - 1, big) == class {
+            index -= 1;
+        }
+        index
+    }
+
+    fn word_end(&self, index: usize, big: bool) -> usize {
+        let len = self.len();
+        let mut next = index + 1;
+        while next < len && self.class(next, big) == 0 {
+            next += 1;
+        }
+        if next >= len {
+            return len.saturating_sub(1).max(index);
+        }
+        let class = self.class(next, big);
+        while next + 1 < len && self.class(next + 1, big) == class {
+            next += 1;
+        }
+        next
+    }
+
+    fn first_non_blank(&self) -> usize {
+        self.cells
+            .iter()
+            .position(|&(_, c)| !c.is_whitespace())
+            .unwrap_or(self.len())
+    }
+
+    // the `count`th `target` after or before `index`, or the character next to it for `till`
+    fn find(
+        &self,
+        index: usize,
+        count: usize,
+        target: char,
+        forward: bool,
+        till: bool,
+    ) -> Option<usize> {
+        let mut found = index;
+        for _ in 0..count {
+            found = if forward {
+                (found + 1..self.len()).find(|&i| self.cells[i].1 == target)?
+            } else {
+                (0..found).rev().find(|&i| self.cells[i].1 == target)?
+            };
+        }
+        match (till, forward) {
+            (false, _) => Some(found),
+            (true, true) => Some(found - 1),
+            (true, false) => Some(found + 1),
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use unicode_width::UnicodeWidthChar;
+
+// Columns a character takes on screen: 2 for wide CJK and emoji, 0 for combining marks and
+// control characters.
+pub fn char_width(c: char) -> usize {
+    c.width().unwrap_or(0)
+}
+
+// Whether `c` goes with the character before it, like an accent. Control characters take no
+// columns either, but a line break or tab in a multi-line entry is a character of its own.
+pub fn is_combining(c: char) -> bool {
+    char_width(c) == 0 && !c.is_control()
+}
+
+// Columns `text` takes on screen, not counting ANSI escape sequences like colours.
+pub fn display_width(text: &str) -> usize {
+    visible_chars(text).map(char_width).sum()
+}
+
+// The characters of `text` that are printed, skipping CSI (`\x1b[...m`) and OSC
+// (`\x1b]...\x07`) sequences.
+pub fn visible_chars(text: &str) -> impl Iterator<Item = char> + '_ {
+    let mut rest = text;
+    std::iter::from_fn(move || loop {
+        let c = rest.chars().next()?;
+        if c == '\x1b' {
+            rest = &rest[escape_length(rest)..];
+            continue;
+        }
+        rest = &rest[c.len_utf8()..];
+        return Some(c);
+    })
+}
+
+// length in bytes of the escape sequence `text` starts with
+fn escape_length(text: &str) -> usize {
+    let mut chars = text.char_indices().skip(1);
+    match chars.next() {
+        Some((_, '[')) => chars
+            .find(|&(_, c)| ('\x40'..='\x7e').contains(&c))
+            .map_or(text.len(), |(offset, _)| offset + 1),
+        Some((_, ']')) => {
+            while let Some((offset, c)) = chars.next() {
+                if c == '\x07' {
+                    return offset + 1;
+                }
+                if c == '\x1b' {
+                    return chars
+                        .next()
+                        .map_or(text.len(), |(offset, c)| offset + c.len_utf8());
+                }
+            }
+            text.len()
+        }
+        Some((offset, c)) => offset + c.len_utf8(),
+        None => text.len(),
+    }
+}
+
+// Where the cursor ends up after printing `text` from `(row, column)` on a terminal
+// `columns` wide. A full row leaves the column at `columns`: terminals only wrap once the
+// next character arrives, and a wide character that doesn't fit moves to the next row whole.
+pub fn advance(
+    text: &str,
+    columns: usize,
+    (mut row, mut column): (usize, usize),
+) -> (usize, usize) {
+    for c in visible_chars(text) {
+        if c == '\n' {
+            row += 1;
+            column = 0;
+            continue;
+        }
+        let width = char_width(c);
+        if width == 0 {
+            continue;
+        }
+        if column + width > columns {
+            row += 1;
+            column = 0;
+        }
+        column += width;
+    }
+    (row, column)
+}
+
+// The longest start of `text` that fits in `columns`, with its width. Escape sequences take
+// no room, but one cut off may leave colours on.
+pub fn truncate(text: &str, columns: usize) -> (&str, usize) {
+    let mut used = 0;
+    let mut offset = 0;
+    while let Some(c) = text[offset..].chars().next() {
+        if c == '\x1b' {
+            offset += escape_length(&text[offset..]);
+            continue;
+        }
+        let c_width = char_width(c);
+        if used + c_width > columns {
+            return (&text[..offset], used);
+        }
+        used += c_width;
+        offset += c.len_utf8();
+    }
+    (text, used)
+}
@@ -0,0 +1,469 @@
+use crate::interpreter::word::{glob_match, line_words};
+use crate::interpreter::{Parser, Stmt, Tokenizer};
+use crate::utils::time;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::fd::AsRawFd;
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub const DEFAULT_SIZE: usize = 10_000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub command: String,
+    // seconds since the Unix epoch when the command started
+    pub timestamp: u64,
+    pub cwd: String,
+    pub status: i32,
+    pub duration: Duration,
+}
+
+impl Entry {
+    // a command starting now in the current directory
+    pub fn new(command: &str) -> Self {
+        Entry {
+            command: command.to_string(),
+            timestamp: time::now(),
+            cwd: std::env::current_dir()
+                .map(|cwd| cwd.display().to_string())
+                .unwrap_or_default(),
+            status: 0,
+            duration: Duration::ZERO,
+        }
+    }
+
+    // one line of the history file: timestamp, duration in ms, status, cwd and command,
+    // separated by tabs with tabs, line breaks and backslashes escaped
+    fn to_line(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}\n",
+            self.timestamp,
+            self.duration.as_millis(),
+            self.status,
+            escape(&self.cwd),
+            escape(&self.command)
+        )
+    }
+
+    fn parse(line: &str) -> Option<Entry> {
+        let mut fields = line.splitn(5, '\t');
+        Some(Entry {
+            timestamp: fields.next()?.parse().ok()?,
+            duration: Duration::from_millis(fields.next()?.parse().ok()?),
+            status: fields.next()?.parse().ok()?,
+            cwd: unescape(fields.next()?),
+            command: unescape(fields.next()?),
+        })
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+}
+
+fn unescape(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => result.push('\t'),
+            Some('n') => result.push('\n'),
+            Some(other) => result.push(other),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+// what replaces a secret in the history file
+const MASK: &str = "***";
+
+// Glob patterns for secrets that must not reach the history file, matched ignoring case.
+// Commands matching one of `ignore` are only kept for this session. Words matching one of
+// `redact`, or whose value after `=` does, are written with the secret masked.
+#[derive(Clone, Debug)]
+pub struct Redactions {
+    pub ignore: Vec<String>,
+    pub redact: Vec<String>,
+}
+
+impl Default for Redactions {
+    fn default() -> Self {
+        let ignore = ["*-----begin*private key*"];
+        let redact = [
+            "*password=*",
+            "*passwd=*",
+            "*secret*=*",
+            "*token=*",
+            "*api_key=*",
+            "*apikey=*",
+            // AWS access key ids, long-lived and temporary
+            "akia????????????????",
+            "asia????????????????",
+            // GitHub, GitLab, Slack and OpenAI style tokens
+            "gh[pousr]_*",
+            "github_pat_*",
+            "glpat-*",
+            "xox[abposr]-*",
+            "sk-????????????????*",
+        ];
+        Redactions {
+            ignore: ignore.map(String::from).to_vec(),
+            redact: redact.map(String::from).to_vec(),
+        }
+    }
+}
+
+impl Redactions {
+    // The command as it may be written to disk, or `None` if it must not be written at all.
+    pub fn apply(&self, command: &str) -> Option<String> {
+        let lowered = command.to_lowercase();
+        if self.ignore.iter().any(|pattern| matches(pattern, &lowered)) {
+            return None;
+        }
+
+        // a quoted value is one word, so all of it is masked
+        let mut result = String::new();
+        let mut last = 0;
+        for (start, word) in line_words(command) {
+            result.push_str(&command[last..start]);
+            result.push_str(&self.redact_word(word));
+            last = start + word.len();
+        }
+        result.push_str(&command[last..]);
+        Some(result)
+    }
+
+    // `password=hunter2` becomes `password=***` and a bare token just `***`, keeping quotes
+    // around the value so the line still parses
+    fn redact_word(&self, word: &str) -> String {
+        let lowered = word.to_lowercase();
+        let value_start = word.find('=').map(|equals| equals + 1);
+        let secret = self.redact.iter().any(|pattern| {
+            matches(pattern, &lowered)
+                || value_start.is_some_and(|start| matches(pattern, &lowered[start..]))
+        });
+        if !secret {
+            return word.to_string();
+        }
+
+        let start = value_start.unwrap_or(0);
+        let value = &word[start..];
+        let quote = value.chars().next().filter(|c| matches!(c, '\'' | '"'));
+        let mut result = word[..start].to_string();
+        if let Some(quote) = quote {
+            result.push(quote);
+            result.push_str(MASK);
+            if value.len() > 1 && value.ends_with(quote) {
+                result.push(quote);
+            }
+        } else {
+            result.push_str(MASK);
+        }
+        result
+    }
+}
+
+// whether `line` is a single sosh expression, like `!done`
+fn is_expression(line: &str) -> bool {
+    matches!(
+        Parser::new(Tokenizer::new(line).tokenize())
+            .parse()
+            .as_deref(),
+        Ok([Stmt::Expr(_)])
+    )
+}
+
+fn matches(pattern: &str, text: &str) -> bool {
+    glob_match(&pattern.to_lowercase(), text)
+}
+
+// Commands entered in this and earlier sessions, numbered from 1. With a file, every entry is
+// appended as a single write under a lock, so several shells can share one history.
+#[derive(Debug)]
+pub struct History {
+    entries: Vec<Entry>,
+    path: Option<PathBuf>,
+    // the most entries kept in memory and in the file
+    pub limit: usize,
+    // lines in the file as far as this shell knows, to decide when to compact it
+    file_lines: usize,
+    pub redactions: Redactions,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History {
+            entries: Vec::new(),
+            path: None,
+            limit: DEFAULT_SIZE,
+            file_lines: 0,
+            redactions: Redactions::default(),
+        }
+    }
+}
+
+impl History {
+    // `$XDG_STATE_HOME/sosh/history`, falling back to `~/.local/state/sosh/history`
+    pub fn default_path() -> Option<PathBuf> {
+        let state = match std::env::var_os("XDG_STATE_HOME").filter(|dir| !dir.is_empty()) {
+            Some(state) => PathBuf::from(state),
+            None => PathBuf::from(std::env::var_os("HOME")?).join(".local/state"),
+        };
+        Some(state.join("sosh").join("history"))
+    }
+
+    // Loads the history stored at `path`. A missing file is just an empty history.
+    pub fn open(path: PathBuf, limit: usize) -> Self {
+        let lines = fs::read_to_string(&path).unwrap_or_default();
+        let mut entries: Vec<Entry> = lines.lines().filter_map(Entry::parse).collect();
+        let file_lines = lines.lines().count();
+        entries.drain(..entries.len().saturating_sub(limit));
+        History {
+            entries,
+            path: Some(path),
+            limit,
+            file_lines,
+            redactions: Redactions::default(),
+        }
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // the entry `!number` refers to
+    pub fn get(&self, number: usize) -> Option<&Entry> {
+        self.entries.get(number.checked_sub(1)?)
+    }
+
+    // Records a finished command. Repeating the previous command doesn't add a new entry.
+    // Secrets are kept out of the file as `redactions` say, but stay in memory for this session.
+    pub fn add(&mut self, entry: Entry) -> io::Result<()> {
+        if entry.command.trim().is_empty()
+            || self
+                .entries
+                .last()
+                .is_some_and(|last| last.command == entry.command)
+        {
+            return Ok(());
+        }
+
+        let line = self.file_line(&entry);
+        self.entries.push(entry);
+        self.entries
+            .drain(..self.entries.len().saturating_sub(self.limit));
+
+        let (Some(path), Some(line)) = (&self.path, line) else {
+            return Ok(());
+        };
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        {
+            let _lock = self.lock()?;
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?
+                .write_all(line.as_bytes())?;
+        }
+        self.file_lines += 1;
+
+        // other shells append as well, so the file is trimmed once it's well over the limit
+        if self.file_lines > self.limit + self.limit / 2 {
+            let limit = self.limit;
+            self.rewrite(|lines| {
+                lines.drain(..lines.len().saturating_sub(limit));
+            })?;
+        }
+        Ok(())
+    }
+
+    // Deletes entry `number` here and in the file.
+    pub fn remove(&mut self, number: usize) -> io::Result<Entry> {
+        let Some(index) = number.checked_sub(1).filter(|&i| i < self.entries.len()) else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{}: history position out of range", number),
+            ));
+        };
+
+        let entry = self.entries.remove(index);
+        // the patterns may have changed since the entry was written, so look for it as is too
+        let written = [Some(entry.to_line()), self.file_line(&entry)];
+        self.rewrite(|lines| {
+            if let Some(position) = lines
+                .iter()
+                .rposition(|line| written.iter().flatten().any(|other| other == line))
+            {
+                lines.remove(position);
+            }
+        })?;
+        Ok(entry)
+    }
+
+    // Deletes the last `count` entries here and in the file, for when a secret slipped through.
+    pub fn forget(&mut self, count: usize) -> io::Result<Vec<Entry>> {
+        let mut forgotten = Vec::new();
+        for _ in 0..count.min(self.entries.len()) {
+            forgotten.push(self.remove(self.entries.len())?);
+        }
+        Ok(forgotten)
+    }
+
+    // how `entry` is written to the file, if at all
+    fn file_line(&self, entry: &Entry) -> Option<String> {
+        let command = self.redactions.apply(&entry.command)?;
+        Some(
+            Entry {
+                command,
+                ..entry.clone()
+            }
+            .to_line(),
+        )
+    }
+
+    pub fn clear(&mut self) -> io::Result<()> {
+        self.entries.clear();
+        self.rewrite(|lines| lines.clear())
+    }
+
+    // Edits the lines of the history file under the lock, including those other shells added,
+    // and swaps the result in atomically.
+    fn rewrite(&mut self, edit: impl FnOnce(&mut Vec<String>)) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let _lock = self.lock()?;
+
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(error) => return Err(error),
+        };
+        let mut lines: Vec<String> = contents.lines().map(|line| format!("{}\n", line)).collect();
+        edit(&mut lines);
+
+        let temporary = path.with_extension(format!("tmp{}", std::process::id()));
+        fs::write(&temporary, lines.concat())?;
+        fs::rename(&temporary, path)?;
+        self.file_lines = lines.len();
+        Ok(())
+    }
+
+    // An exclusive lock on a file next to the history, released when the file is dropped. The
+    // history itself can't carry the lock since rewriting replaces it.
+    fn lock(&self) -> io::Result<File> {
+        let path = self.path.as_ref().expect("only file backed histories lock");
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path.with_extension("lock"))?;
+        // SAFETY: plain system call on a descriptor we own
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(file)
+    }
+
+    // Expands history references in an input line: `!!` is the previous command, `!n` entry n
+    // and `!-n` the nth last one. A line starting with `!prefix` becomes the last command
+    // starting with that prefix, unless the line is an expression like `!done`, since `!` is
+    // also sosh's not operator. Nothing in single or double quotes is expanded, as sosh strings
+    // are taken as they're written. Returns `None` when nothing was expanded.
+    pub fn expand(&self, line: &str) -> Result<Option<String>, String> {
+        let chars: Vec<char> = line.chars().collect();
+        let mut result = String::new();
+        let mut expanded = false;
+        let mut quote = None;
+        let mut i = 0;
+
+        while i < chars.len() {
+            let c = chars[i];
+            match (c, quote) {
+                ('\\', Some('"') | None) if i + 1 < chars.len() => {
+                    result.push(c);
+                    result.push(chars[i + 1]);
+                    i += 2;
+                    continue;
+                }
+                (c, Some(open)) if c == open => quote = None,
+                ('\'' | '"', None) => quote = Some(c),
+                _ => {}
+            }
+            if c != '!' || quote.is_some() {
+                result.push(c);
+                i += 1;
+                continue;
+            }
+
+            let rest = &chars[i + 1..];
+            let digits = rest.iter().take_while(|c| c.is_ascii_digit()).count();
+            let at_start = result.trim().is_empty();
+            let (entry, length) = match rest.first() {
+                Some('!') => (self.entries.last(), 1),
+                Some(c) if c.is_ascii_digit() => {
+                    let number: String = rest[..digits].iter().collect();
+                    (number.parse().ok().and_then(|n| self.get(n)), digits)
+                }
+                Some('-') if rest.get(1).is_some_and(char::is_ascii_digit) => {
+                    let back = rest[1..].iter().take_while(|c| c.is_ascii_digit()).count();
+                    let number: String = rest[1..=back].iter().collect();
+                    let entry = number
+                        .parse()
+                        .ok()
+                        .and_then(|n: usize| self.entries.len().checked_sub(n))
+                        .and_then(|index| self.entries.get(index));
+                    (entry, back + 1)
+                }
+                Some(c)
+                    if at_start
+                        && !c.is_whitespace()
+                        && !matches!(c, '=' | '(')
+                        && !is_expression(line) =>
+                {
+                    let length = rest.iter().take_while(|c| !c.is_whitespace()).count();
+                    let prefix: String = rest[..length].iter().collect();
+                    let entry = self
+                        .entries
+                        .iter()
+                        .rev()
+                        .find(|entry| entry.command.starts_with(&prefix));
+                    (entry, length)
+                }
+                _ => {
+                    result.push(c);
+                    i += 1;
+                    continue;
+                }
+            };
+
+            let Some(entry) = entry else {
+                let event: String = chars[i..=i + length].iter().collect();
+                return Err(format!("{}: event not found", event));
+            };
+            result.push_str(&entry.command);
+            expanded = true;
+            i += 1 + length;
+        }
+
+        Ok(expanded.then_some(result))
+    }
+}
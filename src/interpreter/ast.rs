@@ -42,6 +42,38 @@ pub enum Stmt {
     Function(String, Vec<String>, Box<Stmt>),
     Return(Option<Expr>),
     Block(Vec<Stmt>),
+    Command(Command),
+    Pipeline(Vec<Command>),
+    // a pipeline of one or more commands started with a trailing `&`
+    Background(Vec<Command>),
+}
+
+// words are kept as raw source text and only expanded when the command runs
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Command {
+    pub assignments: Vec<(String, String)>,
+    pub words: Vec<String>,
+    pub redirects: Vec<Redirect>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Redirect {
+    pub fd: i32,
+    pub kind: RedirectKind,
+    pub target: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RedirectKind {
+    Input,
+    Output,
+    Append,
+    // `fd` becomes a copy of the descriptor named by the target, or is closed for `-`
+    Duplicate,
+    // the target is the body, with `$` substitution unless the delimiter was quoted
+    HereDoc { expand: bool },
+    // the target is a word whose expansion, plus a newline, becomes the input
+    HereString,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -0,0 +1,400 @@
+use crate::interpreter::commands::parse_alias;
+use crate::interpreter::evaluator::{Interpreter, RuntimeError};
+use crate::interpreter::exec;
+use crate::interpreter::jobs::{describe_state, JobState};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+pub type Builtin = fn(&mut Interpreter, &[String]) -> Result<i32, RuntimeError>;
+
+// one added with `Interpreter::define_builtin`, which can hold on to state of its own
+pub type AddedBuiltin = Rc<dyn Fn(&mut Interpreter, &[String]) -> Result<i32, RuntimeError>>;
+
+pub const BUILTINS: &[(&str, Builtin)] = &[
+    ("alias", alias),
+    ("bg", bg),
+    ("cd", cd),
+    ("disown", disown),
+    ("exit", exit),
+    ("export", export),
+    ("fg", fg),
+    ("jobs", jobs),
+    ("kill", kill),
+    ("set", set),
+    ("unalias", unalias),
+    ("wait", wait),
+];
+
+const SIGNALS: &[(&str, i32)] = &[
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("USR2", libc::SIGUSR2),
+    ("PIPE", libc::SIGPIPE),
+    ("ALRM", libc::SIGALRM),
+    ("TERM", libc::SIGTERM),
+    ("CHLD", libc::SIGCHLD),
+    ("CONT", libc::SIGCONT),
+    ("STOP", libc::SIGSTOP),
+    ("TSTP", libc::SIGTSTP),
+    ("TTIN", libc::SIGTTIN),
+    ("TTOU", libc::SIGTTOU),
+    ("WINCH", libc::SIGWINCH),
+];
+
+pub fn lookup(name: &str) -> Option<Builtin> {
+    BUILTINS
+        .iter()
+        .find(|(builtin, _)| *builtin == name)
+        .map(|(_, function)| *function)
+}
+
+fn cd(_: &mut Interpreter, args: &[String]) -> Result<i32, RuntimeError> {
+    let target = match args.first().map(String::as_str) {
+        None => std::env::var("HOME").map(PathBuf::from),
+        Some("-") => std::env::var("OLDPWD").map(PathBuf::from),
+        Some(path) => Ok(PathBuf::from(path)),
+    };
+    let Ok(target) = target else {
+        eprintln!("cd: no target directory");
+        return Ok(1);
+    };
+
+    let previous = std::env::current_dir().ok();
+    if let Err(error) = std::env::set_current_dir(&target) {
+        eprintln!("cd: {}: {}", target.display(), error);
+        return Ok(1);
+    }
+
+    if let Some(previous) = previous {
+        std::env::set_var("OLDPWD", previous);
+    }
+    if let Ok(current) = std::env::current_dir() {
+        std::env::set_var("PWD", current);
+    }
+    Ok(0)
+}
+
+fn exit(interpreter: &mut Interpreter, args: &[String]) -> Result<i32, RuntimeError> {
+    let code = match args.first() {
+        Some(code) => match code.parse() {
+            Ok(code) => code,
+            Err(_) => {
+                eprintln!("exit: {}: numeric argument required", code);
+                2
+            }
+        },
+        None => interpreter.last_status(),
+    };
+    Err(RuntimeError::Exit(code))
+}
+
+fn export(interpreter: &mut Interpreter, args: &[String]) -> Result<i32, RuntimeError> {
+    if args.is_empty() {
+        let mut variables: Vec<(String, String)> = std::env::vars().collect();
+        variables.sort();
+        for (name, value) in variables {
+            println!("export {}={:?}", name, value);
+        }
+        return Ok(0);
+    }
+
+    for arg in args {
+        match arg.split_once('=') {
+            Some((name, value)) => std::env::set_var(name, value),
+            // exporting a plain name publishes the shell variable of the same name
+            None => match interpreter.get(arg) {
+                Some(value) => std::env::set_var(arg, value.to_string()),
+                None if std::env::var_os(arg).is_some() => {}
+                None => {
+                    eprintln!("export: {}: not set", arg);
+                    return Ok(1);
+                }
+            },
+        }
+    }
+    Ok(0)
+}
+
+fn set(interpreter: &mut Interpreter, args: &[String]) -> Result<i32, RuntimeError> {
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        let enabled = match flag.as_str() {
+            "-o" => true,
+            "+o" => false,
+            _ => {
+                eprintln!("set: {}: unsupported flag, expected -o or +o", flag);
+                return Ok(2);
+            }
+        };
+
+        let Some(name) = args.next() else {
+            for (name, enabled) in interpreter.options.names() {
+                println!("{:<12}{}", name, if enabled { "on" } else { "off" });
+            }
+            return Ok(0);
+        };
+        if let Err(error) = interpreter.options.set(name, enabled) {
+            eprintln!("set: {}", error);
+            return Ok(1);
+        }
+    }
+    Ok(0)
+}
+
+fn jobs(interpreter: &mut Interpreter, _: &[String]) -> Result<i32, RuntimeError> {
+    interpreter.jobs.poll();
+    let pipefail = interpreter.options.pipefail;
+    for (index, job) in interpreter.jobs.iter().enumerate() {
+        let state = match job.state(pipefail) {
+            JobState::Running => "Running".to_string(),
+            JobState::Stopped => "Stopped".to_string(),
+            JobState::Done(status) => describe_state(status),
+        };
+        let marker = interpreter.jobs.marker(index);
+        println!("[{}]{}  {:<24}{}", job.id, marker, state, job.command);
+    }
+
+    // finished jobs were just reported, so there's nothing left to notify about
+    interpreter
+        .jobs
+        .retain(|job| !matches!(job.state(pipefail), JobState::Done(_)));
+    Ok(0)
+}
+
+// finds the job named by `spec`, reporting a missing one under the builtin's name
+fn find_job(interpreter: &Interpreter, builtin: &str, spec: Option<&String>) -> Option<usize> {
+    match interpreter.jobs.find(spec.map(String::as_str)) {
+        Ok(index) => Some(index),
+        Err(error) => {
+            eprintln!("{}: {}", builtin, error);
+            None
+        }
+    }
+}
+
+fn fg(interpreter: &mut Interpreter, args: &[String]) -> Result<i32, RuntimeError> {
+    if !interpreter.jobs.control() {
+        eprintln!("fg: no job control");
+        return Ok(1);
+    }
+    let Some(index) = find_job(interpreter, "fg", args.first()) else {
+        return Ok(1);
+    };
+
+    let mut job = interpreter.jobs.remove(index);
+    println!("{}", job.command);
+    // the job gets the terminal before it continues, so it doesn't stop again on a read
+    exec::set_foreground(job.pgid);
+    if let Err(error) = job.resume() {
+        eprintln!("fg: {}", error);
+    }
+    // the terminal may have changed size while the job wasn't there to hear about it
+    let _ = job.signal(libc::SIGWINCH);
+    Ok(interpreter.wait_foreground(job))
+}
+
+fn bg(interpreter: &mut Interpreter, args: &[String]) -> Result<i32, RuntimeError> {
+    if !interpreter.jobs.control() {
+        eprintln!("bg: no job control");
+        return Ok(1);
+    }
+    let Some(index) = find_job(interpreter, "bg", args.first()) else {
+        return Ok(1);
+    };
+
+    let marker = interpreter.jobs.marker(index);
+    let job = interpreter.jobs.get_mut(index);
+    if let Err(error) = job.resume() {
+        eprintln!("bg: {}", error);
+        return Ok(1);
+    }
+    println!("[{}]{} {} &", job.id, marker, job.command);
+    Ok(0)
+}
+
+// `kill [-SIGNAL] %job|pid...`, where the signal is a name like `TERM`/`SIGTERM` or a number
+fn kill(interpreter: &mut Interpreter, args: &[String]) -> Result<i32, RuntimeError> {
+    let (signal, targets) = match args.first().and_then(|arg| arg.strip_prefix('-')) {
+        Some(name) => {
+            let number = name.parse().ok().or_else(|| {
+                let name = name.strip_prefix("SIG").unwrap_or(name);
+                SIGNALS
+                    .iter()
+                    .find(|(signal, _)| signal.eq_ignore_ascii_case(name))
+                    .map(|(_, number)| *number)
+            });
+            let Some(number) = number else {
+                eprintln!("kill: {}: invalid signal specification", name);
+                return Ok(1);
+            };
+            (number, &args[1..])
+        }
+        None => (libc::SIGTERM, args),
+    };
+
+    if targets.is_empty() {
+        eprintln!("kill: usage: kill [-SIGNAL] %job|pid...");
+        return Ok(2);
+    }
+
+    let mut status = 0;
+    for target in targets {
+        let result = if target.starts_with('%') {
+            let Some(index) = find_job(interpreter, "kill", Some(target)) else {
+                status = 1;
+                continue;
+            };
+            let job = interpreter.jobs.get(index);
+            // a stopped job has to run to act on most signals
+            job.signal(signal).and_then(|_| match job.state(false) {
+                JobState::Stopped if signal != libc::SIGKILL => job.signal(libc::SIGCONT),
+                _ => Ok(()),
+            })
+        } else {
+            match target.parse() {
+                Ok(pid) => exec::send_signal(pid, signal),
+                Err(_) => {
+                    eprintln!("kill: {}: arguments must be process or job IDs", target);
+                    status = 1;
+                    continue;
+                }
+            }
+        };
+        if let Err(error) = result {
+            eprintln!("kill: {}: {}", target, error);
+            status = 1;
+        }
+    }
+    Ok(status)
+}
+
+// waits for the given jobs or process ids, or for every job, and yields the last one's status
+fn wait(interpreter: &mut Interpreter, args: &[String]) -> Result<i32, RuntimeError> {
+    let pipefail = interpreter.options.pipefail;
+    if args.is_empty() {
+        while !interpreter.jobs.is_empty() {
+            let mut job = interpreter.jobs.remove(0);
+            if !job.wait() {
+                interpreter.jobs.add(job);
+                break;
+            }
+        }
+        return Ok(0);
+    }
+
+    let mut status = 0;
+    for arg in args {
+        let index = if arg.starts_with('%') {
+            find_job(interpreter, "wait", Some(arg))
+        } else {
+            let pid = arg.parse().unwrap_or(0);
+            let index = interpreter
+                .jobs
+                .iter()
+                .position(|job| job.processes.iter().any(|process| process.pid == pid));
+            if index.is_none() {
+                eprintln!("wait: pid {} is not a child of this shell", arg);
+            }
+            index
+        };
+        let Some(index) = index else {
+            status = 127;
+            continue;
+        };
+
+        let mut job = interpreter.jobs.remove(index);
+        if job.wait() {
+            status = job.status(pipefail);
+        } else {
+            status = 128 + libc::SIGTSTP;
+            interpreter.jobs.add(job);
+        }
+    }
+    Ok(status)
+}
+
+// forgets jobs so they no longer show up in `jobs` or get notifications; `-a` forgets them all
+fn disown(interpreter: &mut Interpreter, args: &[String]) -> Result<i32, RuntimeError> {
+    if args.first().is_some_and(|arg| arg == "-a") {
+        interpreter.jobs.retain(|_| false);
+        return Ok(0);
+    }
+
+    let specs: Vec<Option<&String>> = if args.is_empty() {
+        vec![None]
+    } else {
+        args.iter().map(Some).collect()
+    };
+    let mut status = 0;
+    for spec in specs {
+        match find_job(interpreter, "disown", spec) {
+            Some(index) => {
+                interpreter.jobs.remove(index);
+            }
+            None => status = 1,
+        }
+    }
+    Ok(status)
+}
+
+// `alias` lists aliases, `alias name=value` defines one and `alias name` shows it. The value
+// has to be a simple command, whose words replace the name at the start of a command.
+fn alias(interpreter: &mut Interpreter, args: &[String]) -> Result<i32, RuntimeError> {
+    if args.is_empty() {
+        for (name, value) in &interpreter.aliases {
+            println!("alias {}={}", name, quote(value));
+        }
+        return Ok(0);
+    }
+
+    let mut status = 0;
+    for arg in args {
+        let Some((name, value)) = arg.split_once('=') else {
+            match interpreter.aliases.get(arg) {
+                Some(value) => println!("alias {}={}", arg, quote(value)),
+                None => {
+                    eprintln!("alias: {}: not found", arg);
+                    status = 1;
+                }
+            }
+            continue;
+        };
+
+        if name.is_empty() || name.contains(|c: char| c.is_whitespace() || c == '/') {
+            eprintln!("alias: {}: invalid alias name", name);
+            status = 1;
+        } else if parse_alias(value).is_none() {
+            eprintln!("alias: {}: the value must be a simple command", name);
+            status = 1;
+        } else {
+            interpreter
+                .aliases
+                .insert(name.to_string(), value.to_string());
+        }
+    }
+    Ok(status)
+}
+
+fn unalias(interpreter: &mut Interpreter, args: &[String]) -> Result<i32, RuntimeError> {
+    if args.first().is_some_and(|arg| arg == "-a") {
+        interpreter.aliases.clear();
+        return Ok(0);
+    }
+
+    let mut status = 0;
+    for name in args {
+        if interpreter.aliases.remove(name).is_none() {
+            eprintln!("unalias: {}: not found", name);
+            status = 1;
+        }
+    }
+    Ok(status)
+}
+
+// single quotes `value` so `alias` prints something that can be pasted back
+pub fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
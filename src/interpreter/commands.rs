@@ -0,0 +1,411 @@
+use crate::ast::*;
+use crate::interpreter::builtins::{self, AddedBuiltin, Builtin};
+use crate::interpreter::evaluator::{Interpreter, RuntimeError};
+use crate::interpreter::exec::{self, FdAction, ProcessGroup};
+use crate::interpreter::jobs::{describe_pipeline, Job, Process};
+use crate::interpreter::parser::Parser;
+use crate::interpreter::tokenizer::Tokenizer;
+use crate::interpreter::value::Value;
+use crate::interpreter::word::{expand_here_doc, expand_word};
+use std::fs::{File, OpenOptions};
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::slice;
+
+// what the first word of a command refers to
+enum Target {
+    Builtin(Builtin),
+    Added(AddedBuiltin),
+    Function(Value),
+    External,
+    // a lone name that isn't a program echoes the variable of the same name
+    Variable(Value),
+    NotFound,
+}
+
+impl Interpreter {
+    pub(crate) fn expand(&self, raw: &str) -> Vec<String> {
+        expand_word(raw, &|name| self.lookup_variable(name))
+    }
+
+    fn expand_command(&self, command: &Command) -> (Vec<String>, Vec<(String, String)>) {
+        let argv = self
+            .alias_words(&command.words)
+            .iter()
+            .flat_map(|word| self.expand(word))
+            .collect();
+        let assignments = command
+            .assignments
+            .iter()
+            .map(|(name, value)| (name.clone(), self.expand(value).join(" ")))
+            .collect();
+        (argv, assignments)
+    }
+
+    // The raw words of a command with an alias in front replaced by what it stands for. The
+    // replacement isn't looked up again, so `alias ls='ls -F'` works.
+    fn alias_words(&self, words: &[String]) -> Vec<String> {
+        let alias = words.first().and_then(|name| self.aliases.get(name));
+        let Some(Some(mut expanded)) = alias.map(|value| parse_alias(value)) else {
+            return words.to_vec();
+        };
+        expanded.extend_from_slice(&words[1..]);
+        expanded
+    }
+
+    // Every name that can start a command and begins with `prefix`: builtins, aliases, sosh
+    // functions and programs in $PATH, sorted and without duplicates.
+    pub fn command_names(&self, prefix: &str) -> Vec<String> {
+        let mut names: Vec<String> = builtins::BUILTINS
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .chain(self.added_builtins.keys().cloned())
+            .chain(self.aliases.keys().cloned())
+            .chain(self.function_names())
+            .filter(|name| name.starts_with(prefix))
+            .chain(exec::executables(prefix))
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    // whether running `name` on its own would find something to run
+    pub fn command_exists(&self, name: &str) -> bool {
+        self.aliases.contains_key(name)
+            || !matches!(self.resolve(&[name.to_string()]), Target::NotFound)
+    }
+
+    fn resolve(&self, argv: &[String]) -> Target {
+        if let Some(builtin) = builtins::lookup(&argv[0]) {
+            return Target::Builtin(builtin);
+        }
+        if let Some(builtin) = self.added_builtins.get(&argv[0]) {
+            return Target::Added(builtin.clone());
+        }
+        if let Some(function @ Value::Function(_)) = self.get(&argv[0]) {
+            return Target::Function(function);
+        }
+        if exec::find_executable(&argv[0]).is_some() {
+            return Target::External;
+        }
+        match (argv.len(), self.get(&argv[0])) {
+            (1, Some(value)) => Target::Variable(value),
+            _ => Target::NotFound,
+        }
+    }
+
+    // Runs a builtin or function with the command's `NAME=value` assignments in place, as shell
+    // variables when they exist and in the environment of anything it starts, and puts back
+    // what was there before once it's done.
+    fn run_in_process(
+        &mut self,
+        target: Target,
+        argv: &[String],
+        assignments: &[(String, String)],
+    ) -> Result<i32, RuntimeError> {
+        let mut saved = Vec::with_capacity(assignments.len());
+        let mut result = Ok(());
+        for (name, value) in assignments {
+            let variable = self.get(name);
+            saved.push((name, variable.clone(), std::env::var_os(name)));
+            if variable.is_some() {
+                result = self.set_variable(name, Value::String(value.clone()));
+                if result.is_err() {
+                    break;
+                }
+            }
+            std::env::set_var(name, value);
+        }
+
+        let status = result.and_then(|()| self.run_target(target, argv));
+
+        for (name, variable, environment) in saved.into_iter().rev() {
+            if let Some(variable) = variable {
+                let _ = self.set_variable(name, variable);
+            }
+            match environment {
+                Some(value) => std::env::set_var(name, value),
+                None => std::env::remove_var(name),
+            }
+        }
+        status
+    }
+
+    fn run_target(&mut self, target: Target, argv: &[String]) -> Result<i32, RuntimeError> {
+        match target {
+            Target::Builtin(builtin) => builtin(self, &argv[1..]),
+            Target::Added(builtin) => builtin(self, &argv[1..]),
+            // sosh functions can be called like any other command, with words as string arguments
+            Target::Function(function) => {
+                let arguments = argv[1..].iter().cloned().map(Value::String).collect();
+                match self.call(&function, arguments)? {
+                    Value::Nil => Ok(0),
+                    Value::Number(n) => Ok(n as i32),
+                    Value::Bool(b) => Ok(if b { 0 } else { 1 }),
+                    value => {
+                        println!("{}", value);
+                        Ok(0)
+                    }
+                }
+            }
+            Target::Variable(value) => {
+                println!("{}", value);
+                Ok(0)
+            }
+            Target::NotFound => {
+                eprintln!("Error: command {} not found", argv[0]);
+                Ok(127)
+            }
+            Target::External => unreachable!("external commands run in a child process"),
+        }
+    }
+
+    // opens every redirection target of `command`, reporting the first one that fails
+    fn open_redirects(&self, command: &Command) -> Option<Vec<FdAction>> {
+        let mut actions: Vec<FdAction> = Vec::new();
+        for redirect in &command.redirects {
+            let text = match redirect.kind {
+                RedirectKind::HereDoc { expand: true } => {
+                    Some(expand_here_doc(&redirect.target, &|name| {
+                        self.lookup_variable(name)
+                    }))
+                }
+                RedirectKind::HereDoc { expand: false } => Some(redirect.target.clone()),
+                RedirectKind::HereString => Some(self.expand(&redirect.target).join(" ") + "\n"),
+                _ => None,
+            };
+            if let Some(text) = text {
+                match exec::here_document(&text) {
+                    Ok(file) => actions.push(FdAction::Open(redirect.fd, file)),
+                    Err(error) => {
+                        eprintln!("Error: cannot create here-document: {}", describe(&error));
+                        return None;
+                    }
+                }
+                continue;
+            }
+
+            let target = match self.expand(&redirect.target).as_slice() {
+                [target] => target.clone(),
+                _ => {
+                    eprintln!("Error: {}: ambiguous redirect", redirect.target);
+                    return None;
+                }
+            };
+
+            let action = match redirect.kind {
+                RedirectKind::Duplicate if target == "-" => FdAction::Close(redirect.fd),
+                RedirectKind::Duplicate => match target.parse() {
+                    Ok(source)
+                        if exec::is_open(source)
+                            || actions.iter().any(|action| action.fd() == source) =>
+                    {
+                        FdAction::Duplicate(redirect.fd, source)
+                    }
+                    _ => {
+                        eprintln!("Error: {}: not a file descriptor", target);
+                        return None;
+                    }
+                },
+                kind => match open_redirect(kind, &target) {
+                    Ok(file) => FdAction::Open(redirect.fd, file.into()),
+                    Err(error) => {
+                        let verb = match kind {
+                            RedirectKind::Input => "read",
+                            _ => "write to",
+                        };
+                        eprintln!("Error: cannot {} '{}': {}", verb, target, describe(&error));
+                        return None;
+                    }
+                },
+            };
+            actions.push(action);
+        }
+        Some(actions)
+    }
+
+    pub(crate) fn run_command(&mut self, command: &Command) -> Result<i32, RuntimeError> {
+        let (argv, assignments) = self.expand_command(command);
+        let Some(actions) = self.open_redirects(command) else {
+            return Ok(1);
+        };
+
+        if argv.is_empty() {
+            for (name, value) in assignments {
+                self.set_variable(&name, Value::String(value))?;
+            }
+            return Ok(0);
+        }
+
+        let target = self.resolve(&argv);
+        if !matches!(target, Target::External) {
+            // builtins and functions run inside the shell, so its own descriptors are redirected
+            // for the duration of the command
+            let _saved = match exec::redirect_shell(actions) {
+                Ok(saved) => saved,
+                Err(error) => {
+                    eprintln!("Error: {}", describe(&error));
+                    return Ok(1);
+                }
+            };
+            return self.run_in_process(target, &argv, &assignments);
+        }
+
+        let group = self.process_group(0, true);
+        match exec::spawn(&argv, &assignments, actions, group) {
+            Ok(child) => {
+                let pid = child.id() as i32;
+                let pgid = if group.is_some() { pid } else { 0 };
+                let job = Job::new(
+                    pgid,
+                    describe_pipeline(slice::from_ref(command)),
+                    vec![Process::running(pid)],
+                );
+                Ok(self.wait_foreground(job))
+            }
+            Err(error) => {
+                eprintln!("Error: {}: {}", argv[0], error);
+                Ok(126)
+            }
+        }
+    }
+
+    // With job control every job gets its own process group, joined by all of its members.
+    fn process_group(&self, pgid: i32, foreground: bool) -> Option<ProcessGroup> {
+        self.jobs
+            .control()
+            .then_some(ProcessGroup { pgid, foreground })
+    }
+
+    // Connects every member's stdout to the next member's stdin with an OS pipe. Data flows
+    // directly between the processes; builtins and functions run in a forked copy of the shell.
+    // A background pipeline goes into the job table instead of being waited for.
+    pub(crate) fn run_pipeline(
+        &mut self,
+        commands: &[Command],
+        background: bool,
+    ) -> Result<i32, RuntimeError> {
+        let mut processes = Vec::with_capacity(commands.len());
+        let mut stdin: Option<OwnedFd> = None;
+        // the first member that starts leads the process group
+        let mut pgid = 0;
+
+        for (i, command) in commands.iter().enumerate() {
+            let (reader, writer) = if i + 1 < commands.len() {
+                match std::io::pipe() {
+                    Ok((reader, writer)) => (Some(OwnedFd::from(reader)), Some(writer)),
+                    Err(error) => {
+                        eprintln!("Error: cannot create pipe: {}", error);
+                        break;
+                    }
+                }
+            } else {
+                (None, None)
+            };
+
+            let mut actions = Vec::new();
+            actions.extend(stdin.take().map(|fd| FdAction::Open(0, fd)));
+            actions.extend(writer.map(|fd| FdAction::Open(1, OwnedFd::from(fd))));
+
+            let group = self.process_group(pgid, !background);
+            let process = self.start_member(command, actions, reader.as_ref(), group);
+            if group.is_some() && pgid == 0 && process.pid != 0 {
+                pgid = process.pid;
+            }
+            processes.push(process);
+            stdin = reader;
+        }
+
+        let job = Job::new(pgid, describe_pipeline(commands), processes);
+        if !background {
+            return Ok(self.wait_foreground(job));
+        }
+
+        self.last_background = job
+            .processes
+            .iter()
+            .rev()
+            .map(|p| p.pid)
+            .find(|&pid| pid != 0);
+        let index = self.jobs.add(job);
+        if self.jobs.control() {
+            let job = self.jobs.get(index);
+            println!("[{}] {}", job.id, self.last_background.unwrap_or(0));
+        }
+        Ok(0)
+    }
+
+    fn start_member(
+        &mut self,
+        command: &Command,
+        mut actions: Vec<FdAction>,
+        next_stdin: Option<&OwnedFd>,
+        group: Option<ProcessGroup>,
+    ) -> Process {
+        let (argv, assignments) = self.expand_command(command);
+        let Some(redirects) = self.open_redirects(command) else {
+            return Process::finished(1);
+        };
+        actions.extend(redirects);
+
+        if argv.is_empty() {
+            return Process::finished(0);
+        }
+
+        let target = self.resolve(&argv);
+        let started = if let Target::External = target {
+            exec::spawn(&argv, &assignments, actions, group).map(|child| child.id() as i32)
+        } else {
+            exec::fork_with(
+                actions,
+                next_stdin.map(AsRawFd::as_raw_fd),
+                group,
+                || match self.run_in_process(target, &argv, &assignments) {
+                    Ok(status) => status,
+                    Err(RuntimeError::Exit(code)) => code,
+                    Err(error) => {
+                        eprintln!("Error: {}", error);
+                        1
+                    }
+                },
+            )
+        };
+
+        match started {
+            Ok(pid) => Process::running(pid),
+            Err(error) => {
+                eprintln!("Error: {}: {}", argv[0], error);
+                Process::finished(126)
+            }
+        }
+    }
+}
+
+// The words of an alias value, or `None` unless it's a simple command without redirections.
+pub fn parse_alias(value: &str) -> Option<Vec<String>> {
+    let statements = Parser::new(Tokenizer::new(value).tokenize()).parse().ok()?;
+    match statements.as_slice() {
+        [Stmt::Command(command)] if command.redirects.is_empty() => Some(command.words.clone()),
+        _ => None,
+    }
+}
+
+fn open_redirect(kind: RedirectKind, target: &str) -> std::io::Result<File> {
+    match kind {
+        RedirectKind::Input => File::open(target),
+        RedirectKind::Output => File::create(target),
+        RedirectKind::Append => OpenOptions::new().append(true).create(true).open(target),
+        RedirectKind::Duplicate | RedirectKind::HereDoc { .. } | RedirectKind::HereString => {
+            unreachable!("only file redirections open files")
+        }
+    }
+}
+
+// the OS error message without the trailing "(os error N)"
+fn describe(error: &std::io::Error) -> String {
+    let message = error.to_string();
+    match message.split_once(" (os error") {
+        Some((message, _)) => message.to_string(),
+        None => message,
+    }
+}
@@ -0,0 +1,113 @@
+use crate::interpreter::evaluator::RuntimeError;
+use crate::interpreter::value::Value;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// the constants every program starts with, which no scope may define again
+const RESERVED: [(&str, Value); 3] = [
+    ("nil", Value::Nil),
+    ("true", Value::Bool(true)),
+    ("false", Value::Bool(false)),
+];
+
+#[derive(Clone, Debug)]
+struct Binding {
+    value: Value,
+    constant: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct Environment {
+    values: HashMap<String, Binding>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Environment::default()
+    }
+
+    pub fn with_enclosing(enclosing: Rc<RefCell<Environment>>) -> Self {
+        Environment {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    // the outermost scope, holding `nil`, `true` and `false`
+    pub fn global() -> Self {
+        let mut environment = Environment::new();
+        for (name, value) in RESERVED {
+            environment.values.insert(
+                name.to_string(),
+                Binding {
+                    value,
+                    constant: true,
+                },
+            );
+        }
+        environment
+    }
+
+    pub fn define(&mut self, name: &str, value: Value) -> Result<(), RuntimeError> {
+        self.bind(name, value, false)
+    }
+
+    pub fn define_const(&mut self, name: &str, value: Value) -> Result<(), RuntimeError> {
+        self.bind(name, value, true)
+    }
+
+    // A new binding in this scope. It may replace a variable, but a constant stays what it was,
+    // and so do `nil`, `true` and `false` in every scope.
+    fn bind(&mut self, name: &str, value: Value, constant: bool) -> Result<(), RuntimeError> {
+        if RESERVED.iter().any(|(reserved, _)| *reserved == name)
+            || self
+                .values
+                .get(name)
+                .is_some_and(|binding| binding.constant)
+        {
+            return Err(RuntimeError::ConstAssignment(name.to_string()));
+        }
+        self.values
+            .insert(name.to_string(), Binding { value, constant });
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<Value> {
+        match self.values.get(name) {
+            Some(binding) => Some(binding.value.clone()),
+            None => self
+                .enclosing
+                .as_ref()
+                .and_then(|enclosing| enclosing.borrow().get(name)),
+        }
+    }
+
+    // every visible name with its value, inner scopes hiding outer ones
+    pub fn bindings(&self) -> HashMap<String, Value> {
+        let mut bindings = match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().bindings(),
+            None => HashMap::new(),
+        };
+        for (name, binding) in &self.values {
+            bindings.insert(name.clone(), binding.value.clone());
+        }
+        bindings
+    }
+
+    pub fn assign(&mut self, name: &str, value: Value) -> Result<(), RuntimeError> {
+        if let Some(binding) = self.values.get_mut(name) {
+            if binding.constant {
+                return Err(RuntimeError::ConstAssignment(name.to_string()));
+            }
+            binding.value = value;
+            return Ok(());
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+            None => Err(RuntimeError::UndefinedVariable(name.to_string())),
+        }
+    }
+}
@@ -0,0 +1,519 @@
+use crate::ast::*;
+use crate::interpreter::builtins::AddedBuiltin;
+use crate::interpreter::environment::Environment;
+use crate::interpreter::jobs::Jobs;
+use crate::interpreter::options::ShellOptions;
+use crate::interpreter::signals;
+use crate::interpreter::value::*;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::rc::Rc;
+use std::time::Duration;
+
+// How deep blocks and expressions, function bodies and calls among them, may nest while running.
+// Each level takes a good deal of the native stack, so this stays well below what overflows it,
+// even in a debug build.
+pub const MAX_DEPTH: usize = 800;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RuntimeError {
+    UndefinedVariable(String),
+    ConstAssignment(String),
+    InvalidAssignmentTarget,
+    TypeMismatch(String),
+    DivisionByZero,
+    NotCallable(String),
+    ArityMismatch {
+        name: String,
+        expected: usize,
+        got: usize,
+    },
+    ReturnOutsideFunction,
+    // more blocks and expressions nested than `MAX_DEPTH`, as in a function calling itself for good
+    RecursionLimit,
+    // raised by the `exit` builtin and unwinds all the way out of the interpreter
+    Exit(i32),
+    // Ctrl-C while running statements, unwinds back to the prompt
+    Interrupted,
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeError::UndefinedVariable(name) => write!(f, "undefined variable '{}'", name),
+            RuntimeError::ConstAssignment(name) => {
+                write!(f, "cannot assign to constant '{}'", name)
+            }
+            RuntimeError::InvalidAssignmentTarget => write!(f, "invalid assignment target"),
+            RuntimeError::TypeMismatch(message) => write!(f, "type error: {}", message),
+            RuntimeError::DivisionByZero => write!(f, "division by zero"),
+            RuntimeError::NotCallable(what) => write!(f, "{} is not callable", what),
+            RuntimeError::ArityMismatch {
+                name,
+                expected,
+                got,
+            } => write!(
+                f,
+                "{} expects {} argument(s) but got {}",
+                name, expected, got
+            ),
+            RuntimeError::ReturnOutsideFunction => write!(f, "'return' outside of a function"),
+            RuntimeError::RecursionLimit => write!(f, "maximum recursion depth exceeded"),
+            RuntimeError::Exit(code) => write!(f, "exit {}", code),
+            RuntimeError::Interrupted => write!(f, "interrupted"),
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+// how a statement finished, so `return` can unwind through nested blocks
+#[derive(Clone, Debug, PartialEq)]
+pub enum Flow {
+    Normal,
+    Return(Value),
+}
+
+pub struct Interpreter {
+    globals: Rc<RefCell<Environment>>,
+    environment: Rc<RefCell<Environment>>,
+    pub(crate) last_status: i32,
+    pub options: ShellOptions,
+    pub jobs: Jobs,
+    // names defined with `alias`, standing for the start of a command
+    pub aliases: BTreeMap<String, String>,
+    // builtins the program running the interpreter adds for what it keeps, by name
+    pub(crate) added_builtins: BTreeMap<String, AddedBuiltin>,
+    // process id of the most recent background job, for `$!`
+    pub(crate) last_background: Option<i32>,
+    // how long the last line typed took to run, for the prompt
+    pub last_duration: Option<Duration>,
+    // `$0` followed by the positional parameters `$1`, `$2`... of the script being run
+    pub arguments: Vec<String>,
+    // blocks and expressions being run inside each other, to stop runaway recursion before the
+    // stack overflows
+    depth: usize,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        let globals = Rc::new(RefCell::new(Environment::global()));
+        let mut interpreter = Interpreter {
+            globals: globals.clone(),
+            environment: globals,
+            last_status: 0,
+            options: ShellOptions::default(),
+            jobs: Jobs::default(),
+            aliases: BTreeMap::new(),
+            added_builtins: BTreeMap::new(),
+            last_background: None,
+            last_duration: None,
+            arguments: vec!["sosh".to_string()],
+            depth: 0,
+        };
+
+        interpreter.define_native("print", None, native_print);
+        interpreter.define_native("len", Some(1), native_len);
+        interpreter.define_native("str", Some(1), native_str);
+        interpreter.define_native("num", Some(1), native_num);
+
+        interpreter
+    }
+
+    pub fn define_native(&mut self, name: &'static str, arity: Option<usize>, func: NativeFn) {
+        self.globals
+            .borrow_mut()
+            .define(
+                name,
+                Value::NativeFunction(NativeFunction { name, arity, func }),
+            )
+            .expect("native functions don't take the names of constants");
+    }
+
+    // Adds a command that runs in the shell process like the builtins do. The program running
+    // the interpreter uses this for commands about its own state, a builtin of the same name
+    // still comes first.
+    pub fn define_builtin(
+        &mut self,
+        name: &str,
+        builtin: impl Fn(&mut Interpreter, &[String]) -> Result<i32, RuntimeError> + 'static,
+    ) {
+        self.added_builtins
+            .insert(name.to_string(), Rc::new(builtin));
+    }
+
+    pub fn get(&self, name: &str) -> Option<Value> {
+        self.environment.borrow().get(name)
+    }
+
+    // names of the sosh functions visible from the current scope
+    pub fn function_names(&self) -> Vec<String> {
+        self.environment
+            .borrow()
+            .bindings()
+            .into_iter()
+            .filter(|(_, value)| matches!(value, Value::Function(_)))
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    pub fn last_status(&self) -> i32 {
+        self.last_status
+    }
+
+    // for lines that failed before or outside any command, like a syntax error
+    pub fn set_last_status(&mut self, status: i32) {
+        self.last_status = status;
+    }
+
+    // what `$name` expands to in a command word: shell variables win over the environment
+    pub fn lookup_variable(&self, name: &str) -> Option<String> {
+        match name {
+            "?" => return Some(self.last_status.to_string()),
+            "!" => return self.last_background.map(|pid| pid.to_string()),
+            "#" => return Some(self.arguments.len().saturating_sub(1).to_string()),
+            "@" | "*" => return Some(self.arguments.get(1..).unwrap_or_default().join(" ")),
+            _ if name.bytes().all(|b| b.is_ascii_digit()) => {
+                return name
+                    .parse()
+                    .ok()
+                    .and_then(|index: usize| self.arguments.get(index).cloned())
+            }
+            _ => {}
+        }
+        self.get(name)
+            .map(|value| value.to_string())
+            .or_else(|| std::env::var(name).ok())
+    }
+
+    pub(crate) fn set_variable(&mut self, name: &str, value: Value) -> Result<(), RuntimeError> {
+        if std::env::var_os(name).is_some() {
+            std::env::set_var(name, value.to_string());
+        }
+
+        let result = self.environment.borrow_mut().assign(name, value.clone());
+        match result {
+            Err(RuntimeError::UndefinedVariable(_)) => {
+                self.environment.borrow_mut().define(name, value)
+            }
+            result => result,
+        }
+    }
+
+    // runs a whole program and yields the value of the last expression statement
+    pub fn interpret(&mut self, statements: &[Stmt]) -> Result<Value, RuntimeError> {
+        let mut last = Value::Nil;
+        for statement in statements {
+            if let Stmt::Expr(expr) = statement {
+                last = self.evaluate(expr)?;
+                continue;
+            }
+
+            last = Value::Nil;
+            let interrupted = match self.execute(statement) {
+                // Ctrl-C during a command skips the rest of the line
+                Ok(Flow::Normal) => signals::take_interrupt(),
+                Ok(Flow::Return(_)) => return Err(RuntimeError::ReturnOutsideFunction),
+                Err(RuntimeError::Interrupted) => true,
+                Err(error) => return Err(error),
+            };
+            if interrupted {
+                self.last_status = 128 + libc::SIGINT;
+                return Err(RuntimeError::Interrupted);
+            }
+        }
+        Ok(last)
+    }
+
+    pub fn execute(&mut self, stmt: &Stmt) -> Result<Flow, RuntimeError> {
+        match stmt {
+            Stmt::Let(name, initializer) => {
+                let value = self.evaluate(initializer)?;
+                self.environment.borrow_mut().define(name, value)?;
+                Ok(Flow::Normal)
+            }
+            Stmt::Const(name, initializer) => {
+                let value = self.evaluate(initializer)?;
+                self.environment.borrow_mut().define_const(name, value)?;
+                Ok(Flow::Normal)
+            }
+            Stmt::Expr(expr) => {
+                self.evaluate(expr)?;
+                Ok(Flow::Normal)
+            }
+            Stmt::If(condition, then_branch, else_branch) => {
+                if self.evaluate(condition)?.is_truthy() {
+                    self.execute(then_branch)
+                } else if let Some(else_branch) = else_branch {
+                    self.execute(else_branch)
+                } else {
+                    Ok(Flow::Normal)
+                }
+            }
+            Stmt::While(condition, body) => {
+                while self.evaluate(condition)?.is_truthy() {
+                    // Ctrl-C abandons the loop and everything around it
+                    if signals::take_interrupt() {
+                        return Err(RuntimeError::Interrupted);
+                    }
+                    if let Flow::Return(value) = self.execute(body)? {
+                        return Ok(Flow::Return(value));
+                    }
+                }
+                Ok(Flow::Normal)
+            }
+            Stmt::Function(name, params, body) => {
+                let function = Function {
+                    name: name.clone(),
+                    params: params.clone(),
+                    body: (**body).clone(),
+                    closure: self.environment.clone(),
+                };
+                self.environment
+                    .borrow_mut()
+                    .define(name, Value::Function(Rc::new(function)))?;
+                Ok(Flow::Normal)
+            }
+            Stmt::Return(value) => {
+                let value = match value {
+                    Some(expr) => self.evaluate(expr)?,
+                    None => Value::Nil,
+                };
+                Ok(Flow::Return(value))
+            }
+            Stmt::Block(statements) => {
+                let scope = Environment::with_enclosing(self.environment.clone());
+                self.execute_block(statements, Rc::new(RefCell::new(scope)))
+            }
+            Stmt::Command(command) => {
+                self.last_status = self.run_command(command)?;
+                Ok(Flow::Normal)
+            }
+            Stmt::Pipeline(commands) => {
+                self.last_status = self.run_pipeline(commands, false)?;
+                Ok(Flow::Normal)
+            }
+            Stmt::Background(commands) => {
+                self.last_status = self.run_pipeline(commands, true)?;
+                Ok(Flow::Normal)
+            }
+        }
+    }
+
+    fn execute_block(
+        &mut self,
+        statements: &[Stmt],
+        scope: Rc<RefCell<Environment>>,
+    ) -> Result<Flow, RuntimeError> {
+        self.enter()?;
+        let previous = std::mem::replace(&mut self.environment, scope);
+
+        let mut result = Ok(Flow::Normal);
+        for statement in statements {
+            result = self.execute(statement);
+            if !matches!(result, Ok(Flow::Normal)) {
+                break;
+            }
+        }
+
+        self.environment = previous;
+        self.depth -= 1;
+        result
+    }
+
+    // counts one more level of nesting, failing once there are too many
+    fn enter(&mut self) -> Result<(), RuntimeError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(RuntimeError::RecursionLimit);
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    pub fn evaluate(&mut self, expr: &Expr) -> Result<Value, RuntimeError> {
+        self.enter()?;
+        let result = self.evaluate_expr(expr);
+        self.depth -= 1;
+        result
+    }
+
+    fn evaluate_expr(&mut self, expr: &Expr) -> Result<Value, RuntimeError> {
+        match expr {
+            Expr::Number(n) => Ok(Value::Number(*n)),
+            Expr::String(s) => Ok(Value::String(s.clone())),
+            Expr::Identifier(name) => self
+                .get(name)
+                .ok_or_else(|| RuntimeError::UndefinedVariable(name.clone())),
+            Expr::Assign(target, value) => {
+                let Expr::Identifier(name) = &**target else {
+                    return Err(RuntimeError::InvalidAssignmentTarget);
+                };
+                let value = self.evaluate(value)?;
+                self.environment.borrow_mut().assign(name, value.clone())?;
+                Ok(value)
+            }
+            Expr::Unary(op, right) => {
+                let right = self.evaluate(right)?;
+                match op {
+                    UnOp::Not => Ok(Value::Bool(!right.is_truthy())),
+                    UnOp::Negate => match right {
+                        Value::Number(n) => Ok(Value::Number(-n)),
+                        other => Err(RuntimeError::TypeMismatch(format!(
+                            "cannot negate a {}",
+                            other.type_name()
+                        ))),
+                    },
+                }
+            }
+            Expr::Binary(left, BinOp::And, right) => {
+                let left = self.evaluate(left)?;
+                if !left.is_truthy() {
+                    return Ok(left);
+                }
+                self.evaluate(right)
+            }
+            Expr::Binary(left, BinOp::Or, right) => {
+                let left = self.evaluate(left)?;
+                if left.is_truthy() {
+                    return Ok(left);
+                }
+                self.evaluate(right)
+            }
+            Expr::Binary(left, op, right) => {
+                let left = self.evaluate(left)?;
+                let right = self.evaluate(right)?;
+                binary(left, op, right)
+            }
+            Expr::Call(callee, arguments) => {
+                let callee = self.evaluate(callee)?;
+                let mut values = Vec::with_capacity(arguments.len());
+                for argument in arguments {
+                    values.push(self.evaluate(argument)?);
+                }
+                self.call(&callee, values)
+            }
+        }
+    }
+
+    pub fn call(&mut self, callee: &Value, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+        match callee {
+            Value::Function(function) => {
+                if arguments.len() != function.params.len() {
+                    return Err(RuntimeError::ArityMismatch {
+                        name: function.name.clone(),
+                        expected: function.params.len(),
+                        got: arguments.len(),
+                    });
+                }
+
+                let mut scope = Environment::with_enclosing(function.closure.clone());
+                for (param, argument) in function.params.iter().zip(arguments) {
+                    scope.define(param, argument)?;
+                }
+
+                let statements = match &function.body {
+                    Stmt::Block(statements) => statements.as_slice(),
+                    body => std::slice::from_ref(body),
+                };
+
+                self.enter()?;
+                let flow = self.execute_block(statements, Rc::new(RefCell::new(scope)));
+                self.depth -= 1;
+                match flow? {
+                    Flow::Return(value) => Ok(value),
+                    Flow::Normal => Ok(Value::Nil),
+                }
+            }
+            Value::NativeFunction(native) => {
+                if let Some(arity) = native.arity {
+                    if arguments.len() != arity {
+                        return Err(RuntimeError::ArityMismatch {
+                            name: native.name.to_string(),
+                            expected: arity,
+                            got: arguments.len(),
+                        });
+                    }
+                }
+                (native.func)(self, &arguments)
+            }
+            other => Err(RuntimeError::NotCallable(format!(
+                "a {}",
+                other.type_name()
+            ))),
+        }
+    }
+}
+
+fn binary(left: Value, op: &BinOp, right: Value) -> Result<Value, RuntimeError> {
+    match (op, left, right) {
+        (BinOp::Equal, left, right) => Ok(Value::Bool(left == right)),
+        (BinOp::NotEqual, left, right) => Ok(Value::Bool(left != right)),
+        (BinOp::Add, Value::Number(a), Value::Number(b)) => Ok(Value::Number(a + b)),
+        (BinOp::Add, Value::String(a), b) => Ok(Value::String(format!("{}{}", a, b))),
+        (BinOp::Add, a, Value::String(b)) => Ok(Value::String(format!("{}{}", a, b))),
+        (BinOp::Subtract, Value::Number(a), Value::Number(b)) => Ok(Value::Number(a - b)),
+        (BinOp::Multiply, Value::Number(a), Value::Number(b)) => Ok(Value::Number(a * b)),
+        (BinOp::Divide | BinOp::Modulus, Value::Number(_), Value::Number(0.0)) => {
+            Err(RuntimeError::DivisionByZero)
+        }
+        (BinOp::Divide, Value::Number(a), Value::Number(b)) => Ok(Value::Number(a / b)),
+        (BinOp::Modulus, Value::Number(a), Value::Number(b)) => Ok(Value::Number(a % b)),
+        (BinOp::Less, Value::Number(a), Value::Number(b)) => Ok(Value::Bool(a < b)),
+        (BinOp::Greater, Value::Number(a), Value::Number(b)) => Ok(Value::Bool(a > b)),
+        (BinOp::LessEqual, Value::Number(a), Value::Number(b)) => Ok(Value::Bool(a <= b)),
+        (BinOp::GreaterEqual, Value::Number(a), Value::Number(b)) => Ok(Value::Bool(a >= b)),
+        (BinOp::Less, Value::String(a), Value::String(b)) => Ok(Value::Bool(a < b)),
+        (BinOp::Greater, Value::String(a), Value::String(b)) => Ok(Value::Bool(a > b)),
+        (BinOp::LessEqual, Value::String(a), Value::String(b)) => Ok(Value::Bool(a <= b)),
+        (BinOp::GreaterEqual, Value::String(a), Value::String(b)) => Ok(Value::Bool(a >= b)),
+        (op, left, right) => Err(RuntimeError::TypeMismatch(format!(
+            "unsupported operands for {:?}: {} and {}",
+            op,
+            left.type_name(),
+            right.type_name()
+        ))),
+    }
+}
+
+fn native_print(_: &mut Interpreter, arguments: &[Value]) -> Result<Value, RuntimeError> {
+    let line: Vec<String> = arguments.iter().map(|value| value.to_string()).collect();
+    println!("{}", line.join(" "));
+    Ok(Value::Nil)
+}
+
+fn native_len(_: &mut Interpreter, arguments: &[Value]) -> Result<Value, RuntimeError> {
+    match &arguments[0] {
+        Value::String(s) => Ok(Value::Number(s.chars().count() as f64)),
+        other => Err(RuntimeError::TypeMismatch(format!(
+            "len() expects a string, got a {}",
+            other.type_name()
+        ))),
+    }
+}
+
+fn native_str(_: &mut Interpreter, arguments: &[Value]) -> Result<Value, RuntimeError> {
+    Ok(Value::String(arguments[0].to_string()))
+}
+
+fn native_num(_: &mut Interpreter, arguments: &[Value]) -> Result<Value, RuntimeError> {
+    match &arguments[0] {
+        Value::Number(n) => Ok(Value::Number(*n)),
+        Value::Bool(b) => Ok(Value::Number(if *b { 1.0 } else { 0.0 })),
+        Value::String(s) => {
+            s.trim().parse().map(Value::Number).map_err(|_| {
+                RuntimeError::TypeMismatch(format!("cannot convert '{}' to a number", s))
+            })
+        }
+        other => Err(RuntimeError::TypeMismatch(format!(
+            "cannot convert a {} to a number",
+            other.type_name()
+        ))),
+    }
+}
@@ -0,0 +1,334 @@
+use crate::interpreter::signals;
+use std::fs::File;
+use std::io::{self, Seek, SeekFrom, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Child;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+pub fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+// names containing a '/' are paths, everything else is looked up in $PATH
+pub fn find_executable(name: &str) -> Option<PathBuf> {
+    if name.is_empty() {
+        return None;
+    }
+    if name.contains('/') {
+        let path = PathBuf::from(name);
+        return is_executable(&path).then_some(path);
+    }
+
+    let path = std::env::var("PATH").ok()?;
+    path.split(':')
+        .filter(|directory| !directory.is_empty())
+        .map(|directory| Path::new(directory).join(name))
+        .find(|candidate| is_executable(candidate))
+}
+
+// names of the programs in $PATH starting with `prefix`, for completion
+pub fn executables(prefix: &str) -> Vec<String> {
+    let Ok(path) = std::env::var("PATH") else {
+        return Vec::new();
+    };
+    let mut names = Vec::new();
+    for directory in path.split(':').filter(|directory| !directory.is_empty()) {
+        let Ok(entries) = std::fs::read_dir(directory) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            if name.starts_with(prefix) && is_executable(&entry.path()) {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
+// What a redirection or pipe does to one descriptor of a command, applied in order.
+pub enum FdAction {
+    Open(i32, OwnedFd),
+    // `fd` becomes a copy of the second descriptor, as in `2>&1`
+    Duplicate(i32, i32),
+    Close(i32),
+}
+
+impl FdAction {
+    pub fn fd(&self) -> i32 {
+        match self {
+            FdAction::Open(fd, _) | FdAction::Duplicate(fd, _) | FdAction::Close(fd) => *fd,
+        }
+    }
+}
+
+// the actions as plain descriptor numbers, `None` meaning close
+fn raw_actions(actions: &[FdAction]) -> Vec<(i32, Option<RawFd>)> {
+    actions
+        .iter()
+        .map(|action| match action {
+            FdAction::Open(fd, file) => (*fd, Some(file.as_raw_fd())),
+            FdAction::Duplicate(fd, source) => (*fd, Some(*source)),
+            FdAction::Close(fd) => (*fd, None),
+        })
+        .collect()
+}
+
+// Only makes system calls, so it is safe to run between fork and exec.
+fn apply_raw(actions: &[(i32, Option<RawFd>)]) -> io::Result<()> {
+    for &(fd, source) in actions {
+        // SAFETY: dup2, fcntl and close only touch the descriptor table
+        let result = unsafe {
+            match source {
+                // already in place, it just has to survive exec
+                Some(source) if source == fd => libc::fcntl(fd, libc::F_SETFD, 0),
+                Some(source) => libc::dup2(source, fd),
+                None => {
+                    libc::close(fd);
+                    0
+                }
+            }
+        };
+        if result == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+// Drops the files behind `actions`, except those that were opened right on their target
+// descriptor, which now belong to that descriptor.
+fn release(actions: Vec<FdAction>) {
+    for action in actions {
+        if let FdAction::Open(fd, file) = action {
+            if file.as_raw_fd() == fd {
+                std::mem::forget(file);
+            }
+        }
+    }
+}
+
+pub fn is_open(fd: RawFd) -> bool {
+    // SAFETY: F_GETFD only queries the descriptor
+    unsafe { libc::fcntl(fd, libc::F_GETFD) != -1 }
+}
+
+// Stores `text` in an already unlinked temporary file and returns it rewound, ready to become
+// a command's stdin without the writer blocking on a full pipe.
+pub fn here_document(text: &str) -> io::Result<OwnedFd> {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let path = std::env::temp_dir().join(format!(
+        "sosh-here-{}-{}",
+        std::process::id(),
+        COUNT.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let mut file = File::options()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(&path)?;
+    std::fs::remove_file(&path)?;
+    file.write_all(text.as_bytes())?;
+    file.seek(SeekFrom::Start(0))?;
+    Ok(file.into())
+}
+
+// Starts `argv` with the shell's stdin, stdout and stderr unless a pipe or redirection in
+// `actions` replaced them, so full-screen programs like `vim` and `less` keep talking to the
+// terminal. The actions run in the child right before exec, so later ones see earlier ones.
+pub fn spawn(
+    argv: &[String],
+    env: &[(String, String)],
+    actions: Vec<FdAction>,
+    group: Option<ProcessGroup>,
+) -> io::Result<Child> {
+    let mut command = std::process::Command::new(&argv[0]);
+    command.args(&argv[1..]);
+    command.envs(env.iter().map(|(name, value)| (name, value)));
+
+    let raw = raw_actions(&actions);
+    // SAFETY: `enter_group` and `apply_raw` don't allocate or take locks
+    unsafe {
+        command.pre_exec(move || {
+            enter_group(group);
+            apply_raw(&raw)
+        })
+    };
+
+    // the opened files have to stay open until the child has its own copies
+    let child = command.spawn();
+    drop(actions);
+    if let (Ok(child), Some(group)) = (&child, group) {
+        join_group(child.id() as i32, group);
+    }
+    child
+}
+
+// Runs `body` in a forked copy of the shell with `actions` applied, which is how builtins and
+// sosh functions take part in pipelines. `close` is a descriptor only the parent should keep.
+pub fn fork_with(
+    actions: Vec<FdAction>,
+    close: Option<RawFd>,
+    group: Option<ProcessGroup>,
+    body: impl FnOnce() -> i32,
+) -> io::Result<i32> {
+    io::stdout().flush()?;
+
+    // SAFETY: the shell is single threaded, and the child only runs shell code before `_exit`
+    let pid = unsafe { libc::fork() };
+    if pid == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    if pid == 0 {
+        enter_group(group);
+        if let Err(error) = apply_raw(&raw_actions(&actions)) {
+            eprintln!("Error: {}", error);
+            // SAFETY: see below
+            unsafe { libc::_exit(1) };
+        }
+        release(actions);
+        if let Some(fd) = close {
+            // SAFETY: the descriptor belongs to the parent's copy of the pipeline
+            unsafe { libc::close(fd) };
+        }
+
+        let code = body();
+        let _ = io::stdout().flush();
+        // SAFETY: leaves without running the parent's destructors or atexit handlers
+        unsafe { libc::_exit(code) };
+    }
+
+    if let Some(group) = group {
+        join_group(pid, group);
+    }
+    Ok(pid)
+}
+
+// The shell's own descriptors while a builtin or function runs with redirections. Dropping it
+// puts the originals back.
+pub struct SavedFds(Vec<(i32, Option<OwnedFd>)>);
+
+pub fn redirect_shell(actions: Vec<FdAction>) -> io::Result<SavedFds> {
+    io::stdout().flush()?;
+
+    let mut saved = SavedFds(Vec::new());
+    for (fd, source) in raw_actions(&actions) {
+        // SAFETY: copies the descriptor above the range redirections use
+        let copy = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 10) };
+        // SAFETY: `copy` is a fresh descriptor nobody else owns
+        let copy = (copy != -1).then(|| unsafe { OwnedFd::from_raw_fd(copy) });
+        saved.0.push((fd, copy));
+        apply_raw(&[(fd, source)])?;
+    }
+    release(actions);
+    Ok(saved)
+}
+
+impl Drop for SavedFds {
+    fn drop(&mut self) {
+        let _ = io::stdout().flush();
+        for (fd, copy) in self.0.drain(..).rev() {
+            // SAFETY: restores descriptors this shell owned before the redirection
+            unsafe {
+                match copy {
+                    Some(copy) => libc::dup2(copy.as_raw_fd(), fd),
+                    None => libc::close(fd),
+                }
+            };
+        }
+    }
+}
+
+// Where a new process goes when job control is on. A `pgid` of 0 starts a new group led by
+// the process itself, and a foreground group takes over the terminal.
+#[derive(Clone, Copy, Debug)]
+pub struct ProcessGroup {
+    pub pgid: i32,
+    pub foreground: bool,
+}
+
+// Runs in the child: both sides set the group so neither can observe the child outside of it.
+fn enter_group(group: Option<ProcessGroup>) {
+    // SAFETY: only async-signal-safe calls on this process
+    unsafe {
+        if let Some(group) = group {
+            libc::setpgid(0, group.pgid);
+            if group.foreground {
+                libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpgrp());
+            }
+        }
+    }
+    // the shell ignores or catches signals its children must not
+    signals::reset_in_child();
+}
+
+fn join_group(pid: i32, group: ProcessGroup) {
+    let pgid = if group.pgid == 0 { pid } else { group.pgid };
+    // SAFETY: fails harmlessly once the child has exec'd or exited
+    unsafe { libc::setpgid(pid, pgid) };
+}
+
+// hands the terminal to a process group
+pub fn set_foreground(pgid: i32) {
+    // SAFETY: the shell ignores SIGTTOU, so this can't stop it
+    unsafe { libc::tcsetpgrp(libc::STDIN_FILENO, pgid) };
+}
+
+pub fn send_signal(pid: i32, signal: i32) -> io::Result<()> {
+    // SAFETY: plain system call
+    if unsafe { libc::kill(pid, signal) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProcessState {
+    Exited(i32),
+    Stopped,
+}
+
+// Waits for `pid` to exit or stop. Without `block` it returns `None` for a process that is
+// still running.
+pub fn wait_pid(pid: i32, block: bool) -> io::Result<Option<ProcessState>> {
+    let options = if block {
+        libc::WUNTRACED
+    } else {
+        libc::WUNTRACED | libc::WNOHANG
+    };
+    let mut status = 0;
+    loop {
+        // SAFETY: `status` is a valid out pointer
+        match unsafe { libc::waitpid(pid, &mut status, options) } {
+            0 => return Ok(None),
+            -1 => {
+                let error = io::Error::last_os_error();
+                if error.kind() != io::ErrorKind::Interrupted {
+                    return Err(error);
+                }
+            }
+            _ if libc::WIFSTOPPED(status) => return Ok(Some(ProcessState::Stopped)),
+            _ => return Ok(Some(ProcessState::Exited(exit_code(status)))),
+        }
+    }
+}
+
+// shells report "killed by signal N" as 128 + N
+pub fn exit_code(status: i32) -> i32 {
+    if libc::WIFEXITED(status) {
+        libc::WEXITSTATUS(status)
+    } else if libc::WIFSIGNALED(status) {
+        128 + libc::WTERMSIG(status)
+    } else {
+        1
+    }
+}
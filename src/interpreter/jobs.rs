@@ -0,0 +1,364 @@
+use crate::ast::*;
+use crate::interpreter::evaluator::Interpreter;
+use crate::interpreter::exec::{self, ProcessState};
+use crate::interpreter::signals;
+use std::io::IsTerminal;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JobState {
+    Running,
+    Stopped,
+    Done(i32),
+}
+
+#[derive(Clone, Debug)]
+pub struct Process {
+    // 0 for pipeline members that failed before starting and only have a status
+    pub pid: i32,
+    pub status: Option<i32>,
+    pub stopped: bool,
+}
+
+impl Process {
+    pub fn running(pid: i32) -> Self {
+        Process {
+            pid,
+            status: None,
+            stopped: false,
+        }
+    }
+
+    pub fn finished(status: i32) -> Self {
+        Process {
+            pid: 0,
+            status: Some(status),
+            stopped: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Job {
+    pub id: usize,
+    // 0 when job control is off and the job shares the shell's process group
+    pub pgid: i32,
+    pub command: String,
+    pub processes: Vec<Process>,
+}
+
+impl Job {
+    pub fn new(pgid: i32, command: String, processes: Vec<Process>) -> Self {
+        Job {
+            id: 0,
+            pgid,
+            command,
+            processes,
+        }
+    }
+
+    pub fn state(&self, pipefail: bool) -> JobState {
+        if self.processes.iter().any(|process| process.stopped) {
+            JobState::Stopped
+        } else if self
+            .processes
+            .iter()
+            .all(|process| process.status.is_some())
+        {
+            JobState::Done(self.status(pipefail))
+        } else {
+            JobState::Running
+        }
+    }
+
+    // the last member's status, or with `pipefail` the last non-zero one
+    pub fn status(&self, pipefail: bool) -> i32 {
+        let mut status = 0;
+        for process in &self.processes {
+            let process_status = process.status.unwrap_or(0);
+            if !pipefail || process_status != 0 {
+                status = process_status;
+            }
+        }
+        status
+    }
+
+    // Blocks until every member has exited, returning false as soon as one of them stops.
+    pub fn wait(&mut self) -> bool {
+        for process in self.unfinished() {
+            match exec::wait_pid(process.pid, true) {
+                Ok(Some(ProcessState::Stopped)) => {
+                    process.stopped = true;
+                    return false;
+                }
+                Ok(Some(ProcessState::Exited(status))) => process.status = Some(status),
+                // somebody else reaped it, there's no status left to get
+                Ok(None) | Err(_) => process.status = Some(1),
+            }
+        }
+        true
+    }
+
+    // picks up members that exited or stopped without blocking
+    pub fn poll(&mut self) {
+        for process in self.unfinished() {
+            match exec::wait_pid(process.pid, false) {
+                Ok(Some(ProcessState::Stopped)) => process.stopped = true,
+                Ok(Some(ProcessState::Exited(status))) => {
+                    process.status = Some(status);
+                    process.stopped = false;
+                }
+                Ok(None) => {}
+                Err(_) => process.status = Some(1),
+            }
+        }
+    }
+
+    // sends `signal` to the whole job
+    pub fn signal(&self, signal: i32) -> std::io::Result<()> {
+        if self.pgid != 0 {
+            return exec::send_signal(-self.pgid, signal);
+        }
+        for process in &self.processes {
+            if process.status.is_none() {
+                exec::send_signal(process.pid, signal)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn resume(&mut self) -> std::io::Result<()> {
+        for process in &mut self.processes {
+            process.stopped = false;
+        }
+        self.signal(libc::SIGCONT)
+    }
+
+    fn unfinished(&mut self) -> impl Iterator<Item = &mut Process> {
+        self.processes
+            .iter_mut()
+            .filter(|process| process.status.is_none())
+    }
+}
+
+// Background and stopped jobs, numbered from 1 like in other shells. The most recent job is
+// the current one (`%+`), the one before it the previous one (`%-`).
+#[derive(Debug, Default)]
+pub struct Jobs {
+    jobs: Vec<Job>,
+    // the shell's own process group while job control is on
+    shell_pgid: Option<i32>,
+}
+
+impl Jobs {
+    // Puts the shell in its own process group in the terminal's foreground, so every job can
+    // get a group of its own. Only works when stdin is a terminal.
+    pub fn enable_control(&mut self) -> bool {
+        if !std::io::stdin().is_terminal() {
+            return false;
+        }
+
+        // SAFETY: process group and signal disposition calls on the shell itself
+        unsafe {
+            // wait until a parent shell gives us the terminal
+            loop {
+                let pgid = libc::getpgrp();
+                if libc::tcgetpgrp(libc::STDIN_FILENO) == pgid {
+                    break;
+                }
+                libc::kill(-pgid, libc::SIGTTIN);
+            }
+
+            for signal in [libc::SIGTSTP, libc::SIGTTIN, libc::SIGTTOU] {
+                libc::signal(signal, libc::SIG_IGN);
+            }
+            libc::setpgid(0, 0);
+        }
+
+        // SAFETY: see above
+        let pgid = unsafe { libc::getpgrp() };
+        exec::set_foreground(pgid);
+        self.shell_pgid = Some(pgid);
+        true
+    }
+
+    pub fn control(&self) -> bool {
+        self.shell_pgid.is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Job> {
+        self.jobs.iter()
+    }
+
+    // a job coming back from the foreground keeps its number
+    pub fn add(&mut self, mut job: Job) -> usize {
+        if job.id == 0 {
+            job.id = self.jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1;
+        }
+        self.jobs.push(job);
+        self.jobs.len() - 1
+    }
+
+    pub fn get(&self, index: usize) -> &Job {
+        &self.jobs[index]
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> &mut Job {
+        &mut self.jobs[index]
+    }
+
+    pub fn remove(&mut self, index: usize) -> Job {
+        self.jobs.remove(index)
+    }
+
+    // `+` for the current job, `-` for the previous one
+    pub fn marker(&self, index: usize) -> char {
+        if index + 1 == self.jobs.len() {
+            '+'
+        } else if index + 2 == self.jobs.len() {
+            '-'
+        } else {
+            ' '
+        }
+    }
+
+    // Finds the job a `%` spec refers to: `%n`, `%+`/`%%`, `%-` or `%prefix` of its command.
+    // No spec means the current job.
+    pub fn find(&self, spec: Option<&str>) -> Result<usize, String> {
+        let found = match spec {
+            None | Some("%" | "%%" | "%+") => self.jobs.len().checked_sub(1),
+            Some("%-") => self.jobs.len().checked_sub(2),
+            Some(spec) => {
+                let Some(name) = spec.strip_prefix('%') else {
+                    return Err(format!("{}: not a job spec", spec));
+                };
+                match name.parse::<usize>() {
+                    Ok(id) => self.jobs.iter().position(|job| job.id == id),
+                    Err(_) => self
+                        .jobs
+                        .iter()
+                        .position(|job| job.command.starts_with(name)),
+                }
+            }
+        };
+        found.ok_or_else(|| format!("{}: no such job", spec.unwrap_or("current")))
+    }
+
+    pub fn retain(&mut self, keep: impl FnMut(&Job) -> bool) {
+        self.jobs.retain(keep);
+    }
+
+    pub fn poll(&mut self) {
+        for job in &mut self.jobs {
+            job.poll();
+        }
+    }
+}
+
+impl Interpreter {
+    // Waits for a job that owns the terminal. A job stopped with Ctrl-Z joins the job table
+    // and yields 128 + SIGTSTP like in other shells.
+    pub(crate) fn wait_foreground(&mut self, mut job: Job) -> i32 {
+        if let (Some(shell), true) = (self.jobs.shell_pgid, job.pgid != 0) {
+            exec::set_foreground(job.pgid);
+            let finished = job.wait();
+            exec::set_foreground(shell);
+            if !finished {
+                let index = self.jobs.add(job);
+                let job = &self.jobs.jobs[index];
+                println!("\n[{}]+  {:<24}{}", job.id, "Stopped", job.command);
+                return 128 + libc::SIGTSTP;
+            }
+        } else {
+            job.wait();
+        }
+        let status = job.status(self.options.pipefail);
+        // a job that died from Ctrl-C interrupts the script that started it as well
+        if status == 128 + libc::SIGINT {
+            signals::interrupt();
+        }
+        status
+    }
+
+    // Reports background jobs that finished since the last prompt and forgets them.
+    pub fn notify_jobs(&mut self) {
+        self.jobs.poll();
+        let pipefail = self.options.pipefail;
+        let mut index = 0;
+        while index < self.jobs.len() {
+            if let JobState::Done(status) = self.jobs.jobs[index].state(pipefail) {
+                let marker = self.jobs.marker(index);
+                let job = self.jobs.remove(index);
+                println!(
+                    "[{}]{}  {:<24}{}",
+                    job.id,
+                    marker,
+                    describe_state(status),
+                    job.command
+                );
+            } else {
+                index += 1;
+            }
+        }
+    }
+}
+
+// how `jobs` and notifications describe a finished job, e.g. "Done", "Exit 2" or "Terminated"
+pub fn describe_state(status: i32) -> String {
+    match status {
+        0 => "Done".to_string(),
+        // shells report "killed by signal N" as 128 + N
+        129..=159 => {
+            // SAFETY: strsignal returns a valid string for any signal number
+            let name = unsafe { std::ffi::CStr::from_ptr(libc::strsignal(status - 128)) };
+            name.to_string_lossy().into_owned()
+        }
+        _ => format!("Exit {}", status),
+    }
+}
+
+// Rebuilds a readable command line for `jobs` from the parsed pipeline.
+pub fn describe_pipeline(commands: &[Command]) -> String {
+    commands
+        .iter()
+        .map(describe_command)
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+fn describe_command(command: &Command) -> String {
+    let mut parts: Vec<String> = command
+        .assignments
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .chain(command.words.iter().cloned())
+        .collect();
+
+    for redirect in &command.redirects {
+        let (default_fd, operator) = match redirect.kind {
+            RedirectKind::Input => (0, "<"),
+            RedirectKind::Output => (1, ">"),
+            RedirectKind::Append => (1, ">>"),
+            RedirectKind::Duplicate => (1, ">&"),
+            RedirectKind::HereDoc { .. } => {
+                parts.push("<<…".to_string());
+                continue;
+            }
+            RedirectKind::HereString => (0, "<<<"),
+        };
+        let fd = if redirect.fd == default_fd {
+            String::new()
+        } else {
+            redirect.fd.to_string()
+        };
+        parts.push(format!("{}{}{}", fd, operator, redirect.target));
+    }
+    parts.join(" ")
+}
@@ -1,9 +1,22 @@
 pub mod ast;
+pub mod builtins;
+pub mod commands;
+pub mod environment;
+pub mod evaluator;
+pub mod exec;
+pub mod jobs;
+pub mod options;
 pub mod parser;
+pub mod signals;
 pub mod token_kind;
 pub mod tokenizer;
+pub mod value;
+pub mod word;
 
 pub use ast::*;
+pub use environment::*;
+pub use evaluator::*;
 pub use parser::*;
 pub use token_kind::*;
 pub use tokenizer::*;
+pub use value::*;
@@ -0,0 +1,42 @@
+// switches toggled with `set -o name` / `set +o name`
+#[derive(Clone, Debug)]
+pub struct ShellOptions {
+    // the bar under the line being typed; on unless turned off
+    pub banner: bool,
+    // a pipeline fails with the status of its last failing member instead of the last one
+    pub pipefail: bool,
+    // the line editor uses vi keys instead of emacs ones; `emacs` is the same switch reversed
+    pub vi: bool,
+}
+
+impl Default for ShellOptions {
+    fn default() -> Self {
+        ShellOptions {
+            banner: true,
+            pipefail: false,
+            vi: false,
+        }
+    }
+}
+
+impl ShellOptions {
+    pub fn names(&self) -> Vec<(&'static str, bool)> {
+        vec![
+            ("banner", self.banner),
+            ("emacs", !self.vi),
+            ("pipefail", self.pipefail),
+            ("vi", self.vi),
+        ]
+    }
+
+    pub fn set(&mut self, name: &str, enabled: bool) -> Result<(), String> {
+        match name {
+            "banner" => self.banner = enabled,
+            "emacs" => self.vi = !enabled,
+            "pipefail" => self.pipefail = enabled,
+            "vi" => self.vi = enabled,
+            _ => return Err(format!("{}: invalid option name", name)),
+        }
+        Ok(())
+    }
+}
@@ -3,22 +3,49 @@ use crate::interpreter::token_kind::*;
 
 pub struct Parser {
     tokens: Vec<Token>,
+    // where each token was in the list given to `new`, before comments were dropped
+    origins: Vec<usize>,
     current: usize,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Parser { tokens, current: 0 }
+        let (origins, tokens) = tokens
+            .into_iter()
+            .enumerate()
+            .filter(|(_, token)| token.kind != TokenKind::Comment)
+            .unzip();
+        Parser {
+            tokens,
+            origins,
+            current: 0,
+        }
     }
 
     pub fn parse(&mut self) -> Result<Vec<Stmt>, String> {
+        let statements = self.parse_located()?;
+        Ok(statements.into_iter().map(|(_, stmt)| stmt).collect())
+    }
+
+    // Parses like `parse`, pairing each statement with the index of its first token in the
+    // list given to `new`.
+    pub fn parse_located(&mut self) -> Result<Vec<(usize, Stmt)>, String> {
         let mut statements = Vec::new();
+        self.skip_newlines();
         while !self.is_at_end() {
-            statements.push(self.declaration()?);
+            let start = self.position();
+            statements.push((start, self.declaration()?));
+            self.skip_newlines();
         }
         Ok(statements)
     }
 
+    // index of the token the parser is at in the list given to `new`, which after an error
+    // is where it went wrong
+    pub fn position(&self) -> usize {
+        self.origins[self.current.min(self.origins.len() - 1)]
+    }
+
     fn declaration(&mut self) -> Result<Stmt, String> {
         if self.match_token(&[TokenKind::Keyword(KeywordTokenKind::Let)]) {
             self.let_declaration()
@@ -46,10 +73,7 @@ impl Parser {
             None
         };
 
-        self.consume(
-            TokenKind::Punctuation(PunctuationTokenKind::Semicolon),
-            "Expect ';' after variable declaration.",
-        )?;
+        self.consume_terminator("Expect ';' after variable declaration.")?;
         Ok(Stmt::Let(name, initializer.unwrap_or(Expr::Number(0.0))))
     }
 
@@ -67,10 +91,7 @@ impl Parser {
             "Expect '=' after constant name.",
         )?;
         let initializer = self.expression()?;
-        self.consume(
-            TokenKind::Punctuation(PunctuationTokenKind::Semicolon),
-            "Expect ';' after constant declaration.",
-        )?;
+        self.consume_terminator("Expect ';' after constant declaration.")?;
         Ok(Stmt::Const(name, initializer))
     }
 
@@ -127,11 +148,144 @@ impl Parser {
             self.return_statement()
         } else if self.match_token(&[TokenKind::Punctuation(PunctuationTokenKind::LBrace)]) {
             Ok(self.block()?)
+        } else if self.check(TokenKind::Literal(LiteralTokenKind::Word)) || self.check_redirect() {
+            self.command_statement()
         } else {
             self.expression_statement()
         }
     }
 
+    fn command_statement(&mut self) -> Result<Stmt, String> {
+        let mut commands = vec![self.command()?];
+        // in command context `|` connects commands instead of meaning boolean or
+        while self.match_token(&[TokenKind::Operator(OperatorTokenKind::Or)]) {
+            commands.push(self.command()?);
+        }
+        // a trailing `&` ends the statement by itself
+        if self.match_token(&[TokenKind::Operator(OperatorTokenKind::And)]) {
+            return Ok(Stmt::Background(commands));
+        }
+        self.consume_terminator("Expect ';' or a new line after command.")?;
+
+        if commands.len() == 1 {
+            Ok(Stmt::Command(commands.remove(0)))
+        } else {
+            Ok(Stmt::Pipeline(commands))
+        }
+    }
+
+    fn command(&mut self) -> Result<Command, String> {
+        let mut command = Command::default();
+
+        loop {
+            if self.match_token(&[TokenKind::Literal(LiteralTokenKind::Word)]) {
+                let word = self.previous().value.clone();
+                // `NAME=value` words before the program name set its environment
+                if command.words.is_empty() {
+                    if let Some((name, value)) = split_assignment(&word) {
+                        command
+                            .assignments
+                            .push((name.to_string(), value.to_string()));
+                        continue;
+                    }
+                }
+                command.words.push(word);
+            } else if self.check_redirect() {
+                command.redirects.extend(self.redirect()?);
+            } else {
+                break;
+            }
+        }
+
+        if command.words.is_empty()
+            && command.assignments.is_empty()
+            && command.redirects.is_empty()
+        {
+            return Err(format!("Expect command. Got {:?}", self.peek()));
+        }
+        Ok(command)
+    }
+
+    fn redirect(&mut self) -> Result<Vec<Redirect>, String> {
+        let operator = self.advance().clone();
+        let (default_fd, kind) = match operator.kind {
+            TokenKind::Operator(OperatorTokenKind::Less) => (0, RedirectKind::Input),
+            TokenKind::Operator(OperatorTokenKind::Greater) => (1, RedirectKind::Output),
+            TokenKind::Operator(OperatorTokenKind::DoubleGreater) => (1, RedirectKind::Append),
+            TokenKind::Operator(OperatorTokenKind::DoubleLess) => {
+                (0, RedirectKind::HereDoc { expand: true })
+            }
+            TokenKind::Operator(OperatorTokenKind::TripleLess) => (0, RedirectKind::HereString),
+            _ => unreachable!(),
+        };
+
+        let digits: String = operator
+            .value
+            .chars()
+            .take_while(char::is_ascii_digit)
+            .collect();
+        let fd = if digits.is_empty() {
+            default_fd
+        } else {
+            digits
+                .parse()
+                .map_err(|_| format!("Invalid file descriptor in '{}'.", operator.value))?
+        };
+
+        let target = self
+            .consume(
+                TokenKind::Literal(LiteralTokenKind::Word),
+                &format!("Expect a file name after '{}'.", operator.value),
+            )?
+            .value
+            .clone();
+
+        if let RedirectKind::HereDoc { .. } = kind {
+            let body = self
+                .consume(
+                    TokenKind::Literal(LiteralTokenKind::HereDoc),
+                    "Expect a here-document body.",
+                )?
+                .value
+                .clone();
+            // quoting any part of the delimiter keeps the body literal
+            let expand = !target.contains(['\'', '"', '\\']);
+            return Ok(vec![Redirect {
+                fd,
+                kind: RedirectKind::HereDoc { expand },
+                target: body,
+            }]);
+        }
+
+        // `2>&1` and `<&3` duplicate descriptors
+        if operator.value.len() > 1 && operator.value.ends_with('&') {
+            return Ok(vec![Redirect {
+                fd,
+                kind: RedirectKind::Duplicate,
+                target,
+            }]);
+        }
+
+        let mut redirects = vec![Redirect { fd, kind, target }];
+        // `&>file` and `&>>file` send stderr wherever stdout goes
+        if operator.value.starts_with('&') {
+            redirects.push(Redirect {
+                fd: 2,
+                kind: RedirectKind::Duplicate,
+                target: "1".to_string(),
+            });
+        }
+        Ok(redirects)
+    }
+
+    fn check_redirect(&self) -> bool {
+        self.check(TokenKind::Operator(OperatorTokenKind::Less))
+            || self.check(TokenKind::Operator(OperatorTokenKind::Greater))
+            || self.check(TokenKind::Operator(OperatorTokenKind::DoubleGreater))
+            || self.check(TokenKind::Operator(OperatorTokenKind::DoubleLess))
+            || self.check(TokenKind::Operator(OperatorTokenKind::TripleLess))
+    }
+
     fn if_statement(&mut self) -> Result<Stmt, String> {
         self.consume(
             TokenKind::Punctuation(PunctuationTokenKind::LParen),
@@ -170,23 +324,26 @@ impl Parser {
     }
 
     fn return_statement(&mut self) -> Result<Stmt, String> {
-        let value = if !self.check(TokenKind::Punctuation(PunctuationTokenKind::Semicolon)) {
+        let value = if !self.check(TokenKind::Punctuation(PunctuationTokenKind::Semicolon))
+            && !self.check(TokenKind::Punctuation(PunctuationTokenKind::Newline))
+            && !self.check(TokenKind::Punctuation(PunctuationTokenKind::RBrace))
+            && !self.is_at_end()
+        {
             Some(self.expression()?)
         } else {
             None
         };
-        self.consume(
-            TokenKind::Punctuation(PunctuationTokenKind::Semicolon),
-            "Expect ';' after return value.",
-        )?;
+        self.consume_terminator("Expect ';' after return value.")?;
         Ok(Stmt::Return(value))
     }
 
     fn block(&mut self) -> Result<Stmt, String> {
         let mut statements = Vec::new();
+        self.skip_newlines();
         while !self.check(TokenKind::Punctuation(PunctuationTokenKind::RBrace)) && !self.is_at_end()
         {
             statements.push(self.declaration()?);
+            self.skip_newlines();
         }
         self.consume(
             TokenKind::Punctuation(PunctuationTokenKind::RBrace),
@@ -197,10 +354,7 @@ impl Parser {
 
     fn expression_statement(&mut self) -> Result<Stmt, String> {
         let expr = self.expression()?;
-        self.consume(
-            TokenKind::Punctuation(PunctuationTokenKind::Semicolon),
-            "Expect ';' after expression.",
-        )?;
+        self.consume_terminator("Expect ';' after expression.")?;
         Ok(Stmt::Expr(expr))
     }
 
@@ -235,7 +389,11 @@ impl Parser {
                 let value = if bin_op == BinOp::Equal {
                     value
                 } else {
-                    Expr::Binary(Box::new(Expr::Identifier(name.clone())), bin_op, Box::new(value))
+                    Expr::Binary(
+                        Box::new(Expr::Identifier(name.clone())),
+                        bin_op,
+                        Box::new(value),
+                    )
                 };
                 return Ok(Expr::Assign(Box::new(expr.clone()), Box::new(value)));
             }
@@ -430,7 +588,12 @@ impl Parser {
         }
 
         if self.match_token(&[TokenKind::Literal(LiteralTokenKind::String)]) {
-            let value = self.previous().value[1..self.previous().value.len() - 1].to_string();
+            let raw = &self.previous().value;
+            let value = raw
+                .strip_prefix('"')
+                .and_then(|s| s.strip_suffix('"'))
+                .unwrap_or(raw)
+                .to_string();
 
             return Ok(Expr::String(value));
         }
@@ -497,4 +660,34 @@ impl Parser {
             Err(format!("{} Got {:?}", message, self.peek()))
         }
     }
+
+    // a statement ends with ';' or a line break, or right before the '}' closing its block
+    fn consume_terminator(&mut self, message: &str) -> Result<(), String> {
+        if self.is_at_end() || self.check(TokenKind::Punctuation(PunctuationTokenKind::RBrace)) {
+            return Ok(());
+        }
+        if self.match_token(&[TokenKind::Punctuation(PunctuationTokenKind::Newline)]) {
+            return Ok(());
+        }
+        self.consume(
+            TokenKind::Punctuation(PunctuationTokenKind::Semicolon),
+            message,
+        )?;
+        Ok(())
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.match_token(&[TokenKind::Punctuation(PunctuationTokenKind::Newline)]) {}
+    }
+}
+
+fn split_assignment(word: &str) -> Option<(&str, &str)> {
+    let (name, value) = word.split_once('=')?;
+    let mut chars = name.chars();
+    let valid_start = matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_');
+    if valid_start && chars.all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        Some((name, value))
+    } else {
+        None
+    }
 }
@@ -0,0 +1,66 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// set by Ctrl-C, and by foreground jobs that died from it, until the interpreter notices
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+// set when the terminal changes size, until the line editor redraws for it
+static RESIZED: AtomicBool = AtomicBool::new(false);
+
+// signals a shell changes for itself that its children must get back with default behaviour
+const CHILD_DEFAULTS: [i32; 5] = [
+    libc::SIGINT,
+    libc::SIGQUIT,
+    libc::SIGTSTP,
+    libc::SIGTTIN,
+    libc::SIGTTOU,
+];
+
+extern "C" fn on_interrupt(_: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+extern "C" fn on_resize(_: libc::c_int) {
+    RESIZED.store(true, Ordering::SeqCst);
+}
+
+// Makes Ctrl-C set the interrupt flag instead of killing the shell, and ignores Ctrl-\ and
+// Ctrl-Z. SIGINT doesn't restart system calls, so a blocked read of the prompt returns early.
+// SIGWINCH does, and only cuts short the wait for a key. Programs started get the default
+// handling of both back when they exec.
+pub fn install() {
+    // SAFETY: the handlers only store to atomics
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(libc::SIGINT, &action, std::ptr::null_mut());
+
+        action.sa_sigaction = on_resize as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigaction(libc::SIGWINCH, &action, std::ptr::null_mut());
+
+        libc::signal(libc::SIGQUIT, libc::SIG_IGN);
+        libc::signal(libc::SIGTSTP, libc::SIG_IGN);
+    }
+}
+
+// Runs in new children between fork and exec, so it only makes system calls.
+pub fn reset_in_child() {
+    for signal in CHILD_DEFAULTS {
+        // SAFETY: async-signal-safe
+        unsafe { libc::signal(signal, libc::SIG_DFL) };
+    }
+}
+
+pub fn interrupt() {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+// whether Ctrl-C was pressed since the last call
+pub fn take_interrupt() -> bool {
+    INTERRUPTED.swap(false, Ordering::SeqCst)
+}
+
+// whether the terminal changed size since the last call
+pub fn take_resize() -> bool {
+    RESIZED.swap(false, Ordering::SeqCst)
+}
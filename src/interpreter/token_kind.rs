@@ -53,6 +53,7 @@ pub enum PunctuationTokenKind {
     Semicolon,
     Comma,
     Dot,
+    Newline,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -60,6 +61,10 @@ pub enum LiteralTokenKind {
     Number,
     String,
     Identifier,
+    // a command-mode word, kept as raw source text including quotes and escapes
+    Word,
+    // the body of a here-document, already cut out of the lines that follow its command
+    HereDoc,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -69,6 +74,7 @@ pub enum TokenKind {
     Punctuation(PunctuationTokenKind),
     Literal(LiteralTokenKind),
     Comment,
+    Unknown,
     EOF,
 }
 
@@ -1,12 +1,40 @@
 use crate::interpreter::token_kind::*;
 use crate::utils::IteratorExt;
 use std::collections::HashMap;
+use std::ops::Range;
+
+const OPERATORS: [&str; 26] = [
+    "<<<", ">>>", "==", "!=", "<<", ">>", "<=", ">=", "+=", "-=", "*=", "/=", "%=", "//", "+", "-",
+    "*", "/", "%", "^", "&", "|", "!", "=", "<", ">",
+];
+
+const KEYWORDS: [&str; 11] = [
+    "let", "const", "if", "else", "while", "do", "for", "fun", "return", "break", "continue",
+];
+
+// operators that make `name <op> ...` at the start of a statement an expression
+const ASSIGNMENT_OPERATORS: [&str; 10] =
+    ["==", "!=", "<=", ">=", "+=", "-=", "*=", "/=", "%=", "="];
+const ARITHMETIC_OPERATORS: [char; 5] = ['+', '-', '*', '/', '%'];
 
 #[derive(Clone, Debug)]
 pub struct Tokenizer<'a> {
     input: &'a str,
     position: usize,
     peeked: Option<char>,
+    // a new statement begins here, so the next word decides between command and expression mode
+    statement_start: bool,
+    command_mode: bool,
+    // waiting for the `)` that closes an `if`/`while`/`fun` header
+    in_header: bool,
+    // a header, `else` or `do` came last, so its body is still to come
+    awaiting_body: bool,
+    paren_depth: usize,
+    brace_depth: usize,
+    last_kind: Option<TokenKind>,
+    // where reading continues after the current line, past the bodies of its here-documents
+    here_doc_resume: Option<usize>,
+    unterminated_here_doc: bool,
 }
 
 impl<'a> Iterator for Tokenizer<'a> {
@@ -17,9 +45,8 @@ impl<'a> Iterator for Tokenizer<'a> {
             self.position += c.len_utf8();
             Some(c)
         } else {
-            self.input[self.position..].chars().next().map(|c| {
+            self.input[self.position..].chars().next().inspect(|c| {
                 self.position += c.len_utf8();
-                c
             })
         }
     }
@@ -31,6 +58,15 @@ impl<'a> Tokenizer<'a> {
             input,
             position: 0,
             peeked: None,
+            statement_start: true,
+            command_mode: false,
+            in_header: false,
+            awaiting_body: false,
+            paren_depth: 0,
+            brace_depth: 0,
+            last_kind: None,
+            here_doc_resume: None,
+            unterminated_here_doc: false,
         }
     }
 
@@ -134,37 +170,16 @@ impl<'a> Tokenizer<'a> {
 
     fn read_operator(&mut self) -> Token {
         let start = self.position;
-        let start_c = self.peek().unwrap();
-
-        // advance past the first character
-        self.advance();
 
-        while let Some(c) = self.peek() {
-            match c {
-                '<' | '>' | '/' => {
-                    if start_c == start_c {
-                        self.advance();
-                        // break if // or <<< or >>>
-                        if start_c == '/'
-                            || (matches!(start_c, '<' | '>') && start == self.position - 2)
-                        {
-                            break;
-                        }
-                    }
-                    break;
-                }
-                '=' => {
-                    self.advance();
-                    if start_c == '=' {
-                        break;
-                    }
-                    break;
-                }
-                _ => {
-                    break;
-                }
-            }
+        // longest operator first so that e.g. `<<<` wins over `<<` and `<`
+        let length = OPERATORS
+            .iter()
+            .find(|op| self.input[start..].starts_with(**op))
+            .map_or(1, |op| op.len());
+        for _ in 0..length {
+            self.advance();
         }
+
         let mut value = &self.input[start..self.position];
         let kind = match value {
             "+" => TokenKind::Operator(OperatorTokenKind::Plus),
@@ -214,6 +229,19 @@ impl<'a> Tokenizer<'a> {
         }
     }
 
+    // `#` starts a comment running to the end of the line, like `//` does, when it begins a
+    // word or a statement. That covers a `#!` line at the top of a script.
+    fn read_comment(&mut self) -> Token {
+        let start = self.position;
+        while let Some(c) = self.peek() {
+            if c == '\n' {
+                break;
+            }
+            self.advance();
+        }
+        Token::new(TokenKind::Comment, &self.input[start..self.position])
+    }
+
     fn handle_punctuation(&mut self, c: char) -> Option<Token> {
         let punctuation_map: HashMap<char, PunctuationTokenKind> = HashMap::from([
             ('(', PunctuationTokenKind::LParen),
@@ -229,39 +257,436 @@ impl<'a> Tokenizer<'a> {
 
         self.advance();
 
-        if let Some(kind) = punctuation_map.get(&c) {
-            Some(Token {
-                kind: TokenKind::Punctuation(*kind),
-                value: c.to_string(),
-            })
-        } else {
-            None
+        punctuation_map.get(&c).map(|kind| Token {
+            kind: TokenKind::Punctuation(*kind),
+            value: c.to_string(),
+        })
+    }
+
+    // Decides how the statement starting at the current position is lexed. Script constructs
+    // (keywords, calls, assignments, arithmetic on a name, literals) use expression mode,
+    // anything else is a command line made of words.
+    fn starts_command(&self) -> bool {
+        let rest = &self.input[self.position..];
+        let mut chars = rest.chars();
+        let Some(first) = chars.next() else {
+            return false;
+        };
+
+        match first {
+            '{' | '}' | '(' | ')' | ';' | ',' | '!' => false,
+            // `"a" + "b"` works on a string, while `"my tool" --help` and `"$HOME"/bin/x` run
+            // something, so it takes an operator after a space to make an expression
+            '"' => {
+                let Some(length) = rest[1..].find('"') else {
+                    return true;
+                };
+                let after = &rest[length + 2..];
+                let spaced = after.trim_start_matches([' ', '\t']);
+                if spaced.len() == after.len() {
+                    return true;
+                }
+                let mut spaced = spaced.chars();
+                !match (spaced.next(), spaced.next()) {
+                    (Some('-'), next) => matches!(next, Some(' ' | '\t')),
+                    (Some(c), _) => {
+                        matches!(c, '+' | '*' | '/' | '%' | '^' | '=' | '!' | '<' | '>')
+                    }
+                    (None, _) => false,
+                }
+            }
+            '-' => !matches!(chars.next(), Some('0'..='9' | '.' | '(')),
+            '0'..='9' => {
+                let literal_end = rest
+                    .find(|c: char| !(c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E')))
+                    .unwrap_or(rest.len());
+                matches!(
+                    rest[literal_end..].chars().next(),
+                    Some(c) if c.is_alphanumeric() || matches!(c, '_' | '/' | '-')
+                )
+            }
+            'a'..='z' | 'A'..='Z' | '_' => {
+                let ident_end = rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                if KEYWORDS.contains(&&rest[..ident_end]) {
+                    return false;
+                }
+
+                let after = &rest[ident_end..];
+                if after.starts_with('(') {
+                    return false;
+                }
+                // `x+=1` is an assignment but `FOO=bar cmd` sets the environment of a command
+                if after.starts_with('=') && !after.starts_with("==") {
+                    return true;
+                }
+                if ASSIGNMENT_OPERATORS.iter().any(|op| after.starts_with(op)) {
+                    return false;
+                }
+
+                let spaced = after.trim_start_matches([' ', '\t']);
+                if spaced.len() == after.len() {
+                    return true;
+                }
+                if ASSIGNMENT_OPERATORS.iter().any(|op| spaced.starts_with(op)) {
+                    return false;
+                }
+
+                // `x + 1` is arithmetic while `cat -` or `ls *` stay commands
+                let mut spaced = spaced.chars();
+                match (spaced.next(), spaced.next()) {
+                    (Some(op), Some(' ' | '\t')) if ARITHMETIC_OPERATORS.contains(&op) => {
+                        let operand = spaced.as_str().trim_start_matches([' ', '\t']);
+                        !matches!(operand.chars().next(), Some(c) if !matches!(c, ';' | '\n'))
+                    }
+                    _ => true,
+                }
+            }
+            _ => true,
+        }
+    }
+
+    fn next_expression_token(&mut self, c: char) -> Option<Token> {
+        match c {
+            '\n' => {
+                self.advance();
+                // a line break ends the statement unless it's obviously continued
+                let continued = self.statement_start
+                    || self.paren_depth > 0
+                    || matches!(
+                        self.last_kind,
+                        Some(TokenKind::Operator(_))
+                            | Some(TokenKind::Punctuation(PunctuationTokenKind::Comma))
+                    );
+                (!continued).then(|| {
+                    Token::new(TokenKind::Punctuation(PunctuationTokenKind::Newline), "\n")
+                })
+            }
+            ' ' | '\t' | '\r' => {
+                self.advance();
+                None
+            }
+            'a'..='z' | 'A'..='Z' | '_' => Some(self.read_identifier_or_keyword()),
+            '0'..='9' => Some(self.read_number()),
+            '"' => Some(self.read_string()),
+            '#' => Some(self.read_comment()),
+            '+' | '-' | '*' | '/' | '%' | '^' | '&' | '|' | '!' | '=' | '<' | '>' => {
+                Some(self.read_operator())
+            }
+            _ => Some(
+                self.handle_punctuation(c)
+                    .unwrap_or_else(|| Token::new(TokenKind::Unknown, &c.to_string())),
+            ),
         }
     }
-    pub fn tokenize(&mut self) -> Vec<Token> {
-        let mut tokens = Vec::new();
 
+    fn next_command_token(&mut self, c: char) -> Option<Token> {
+        let rest = &self.input[self.position..];
+        match c {
+            ' ' | '\t' | '\r' => {
+                self.advance();
+                None
+            }
+            // an escaped line break just continues the command on the next line
+            '\\' if rest.starts_with("\\\n") => {
+                self.advance();
+                self.advance();
+                None
+            }
+            '\n' => {
+                self.advance();
+                if let Some(resume) = self.here_doc_resume.take() {
+                    self.position = resume;
+                    self.peeked = None;
+                }
+                Some(Token::new(
+                    TokenKind::Punctuation(PunctuationTokenKind::Newline),
+                    "\n",
+                ))
+            }
+            ';' => self.handle_punctuation(c),
+            '}' if self.brace_depth > 0 => self.handle_punctuation(c),
+            '|' | '&' | '<' | '>' => Some(self.read_command_operator()),
+            // in the middle of a word, like `a#b`, it's just a character
+            '#' => Some(self.read_comment()),
+            // `2>err.log` and `3<&0` name the descriptor right before the operator
+            '0'..='9'
+                if rest
+                    .trim_start_matches(|c: char| c.is_ascii_digit())
+                    .starts_with(['<', '>']) =>
+            {
+                Some(self.read_command_operator())
+            }
+            _ => Some(self.read_word()),
+        }
+    }
+
+    // Reads `|`, `&` and redirection operators. Redirections keep their full text in the token
+    // value, including a leading descriptor (`2>`), `&>` for both outputs and a trailing `&`
+    // for duplication (`2>&`, `<&`).
+    fn read_command_operator(&mut self) -> Token {
+        let rest = &self.input[self.position..];
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let after = &rest[digits..];
+
+        let operator = ["&>>", "&>", "<<<", "<<", ">>", "<", ">", "|", "&"]
+            .into_iter()
+            .find(|op| after.starts_with(op))
+            .unwrap_or_default();
+        let mut length = digits + operator.len();
+        if matches!(operator, "<" | ">" | ">>") && after[operator.len()..].starts_with('&') {
+            length += 1;
+        }
+        // `<<-` strips leading tabs from the here-document
+        if operator == "<<" && after[2..].starts_with('-') {
+            length += 1;
+        }
+
+        let value = &rest[..length];
+        for _ in 0..length {
+            self.advance();
+        }
+
+        let kind = match operator {
+            "<<<" => OperatorTokenKind::TripleLess,
+            "<<" => OperatorTokenKind::DoubleLess,
+            ">>" | "&>>" => OperatorTokenKind::DoubleGreater,
+            "<" => OperatorTokenKind::Less,
+            ">" | "&>" => OperatorTokenKind::Greater,
+            "|" => OperatorTokenKind::Or,
+            _ => OperatorTokenKind::And,
+        };
+        Token::new(TokenKind::Operator(kind), value)
+    }
+
+    // Cuts the body of a here-document out of the lines after the current one (or after the
+    // previous here-document on the same line) up to the line holding only the delimiter.
+    fn read_here_doc(&mut self, delimiter: &str, strip_tabs: bool) -> (Token, Range<usize>) {
+        let delimiter: String = delimiter
+            .chars()
+            .filter(|c| !matches!(c, '\'' | '"' | '\\'))
+            .collect();
+        let start = self.here_doc_resume.unwrap_or_else(|| {
+            self.input[self.position..]
+                .find('\n')
+                .map_or(self.input.len(), |i| self.position + i + 1)
+        });
+
+        let mut body = String::new();
+        let mut end = start;
+        let mut terminated = false;
+        for line in self.input[start..].split_inclusive('\n') {
+            end += line.len();
+            let mut text = line.strip_suffix('\n').unwrap_or(line);
+            if strip_tabs {
+                text = text.trim_start_matches('\t');
+            }
+            if text == delimiter {
+                terminated = true;
+                break;
+            }
+            body.push_str(text);
+            body.push('\n');
+        }
+
+        self.here_doc_resume = Some(end);
+        self.unterminated_here_doc |= !terminated;
+        (
+            Token::new(TokenKind::Literal(LiteralTokenKind::HereDoc), &body),
+            start..end,
+        )
+    }
+
+    // Whether the input stopped before a here-document's delimiter, with a brace or parenthesis
+    // still open, or after a statement header that has no body yet, like `while (i < 2)`. The
+    // shell reads more lines before running it then.
+    pub fn needs_more_input(&self) -> bool {
+        self.unterminated_here_doc
+            || self.paren_depth > 0
+            || self.brace_depth > 0
+            || self.awaiting_body
+            || self.in_header
+    }
+
+    // Reads one shell word. The token keeps the raw text with its quotes and escapes so that
+    // expansion can tell quoted from unquoted parts later on.
+    fn read_word(&mut self) -> Token {
+        let start = self.position;
         while let Some(c) = self.peek() {
             match c {
-                ' ' | '\t' | '\n' | '\r' => self.skip_whitespace(),
-                'a'..='z' | 'A'..='Z' | '_' => tokens.push(self.read_identifier_or_keyword()),
-                '0'..='9' => tokens.push(self.read_number()),
-                '"' => tokens.push(self.read_string()),
-                '+' | '-' | '*' | '/' | '%' | '^' | '&' | '|' | '!' | '=' | '<' | '>' => {
-                    tokens.push(self.read_operator())
+                ' ' | '\t' | '\r' | '\n' | ';' | '|' | '&' | '<' | '>' => break,
+                '\\' => {
+                    self.advance();
+                    self.advance();
+                }
+                '\'' => {
+                    self.advance();
+                    while let Some(c) = self.advance() {
+                        if c == '\'' {
+                            break;
+                        }
+                    }
+                }
+                '"' => {
+                    self.advance();
+                    while let Some(c) = self.advance() {
+                        match c {
+                            '\\' => {
+                                self.advance();
+                            }
+                            '"' => break,
+                            _ => {}
+                        }
+                    }
                 }
                 _ => {
-                    if let Some(token) = self.handle_punctuation(c) {
-                        tokens.push(token);
+                    self.advance();
+                }
+            }
+        }
+
+        Token::new(
+            TokenKind::Literal(LiteralTokenKind::Word),
+            &self.input[start..self.position],
+        )
+    }
+
+    // keeps track of where statements start so the mode can switch mid-line
+    fn track(&mut self, token: &Token) {
+        if !matches!(
+            token.kind,
+            TokenKind::Punctuation(PunctuationTokenKind::Newline)
+                | TokenKind::Comment
+                | TokenKind::EOF
+        ) {
+            self.awaiting_body = false;
+        }
+        match token.kind {
+            TokenKind::Punctuation(PunctuationTokenKind::LParen) => self.paren_depth += 1,
+            TokenKind::Punctuation(PunctuationTokenKind::RParen) => {
+                self.paren_depth = self.paren_depth.saturating_sub(1);
+                if self.paren_depth == 0 && self.in_header {
+                    self.in_header = false;
+                    self.awaiting_body = true;
+                    self.statement_start = true;
+                }
+            }
+            TokenKind::Punctuation(PunctuationTokenKind::LBrace) => {
+                self.brace_depth += 1;
+                self.statement_start = true;
+            }
+            TokenKind::Punctuation(PunctuationTokenKind::RBrace) => {
+                self.brace_depth = self.brace_depth.saturating_sub(1);
+                self.statement_start = true;
+            }
+            TokenKind::Punctuation(PunctuationTokenKind::Semicolon)
+            | TokenKind::Punctuation(PunctuationTokenKind::Newline) => {
+                self.statement_start = true;
+            }
+            TokenKind::Operator(OperatorTokenKind::Or | OperatorTokenKind::And)
+                if self.command_mode =>
+            {
+                self.statement_start = true;
+            }
+            TokenKind::Keyword(
+                KeywordTokenKind::If | KeywordTokenKind::While | KeywordTokenKind::Function,
+            ) => self.in_header = true,
+            TokenKind::Keyword(KeywordTokenKind::Else | KeywordTokenKind::Do) => {
+                self.awaiting_body = true;
+                self.statement_start = true;
+            }
+            _ => {}
+        }
+
+        if self.statement_start {
+            self.command_mode = false;
+        }
+        if token.kind != TokenKind::Comment {
+            self.last_kind = Some(token.kind);
+        }
+    }
+
+    pub fn tokenize(&mut self) -> Vec<Token> {
+        self.tokenize_with_spans()
+            .into_iter()
+            .map(|(token, _)| token)
+            .collect()
+    }
+
+    // Tokenizes like `tokenize`, pairing every token with the byte range of the input it was
+    // read from, quotes included. Here-document bodies cover the lines they were cut from.
+    pub fn tokenize_with_spans(&mut self) -> Vec<(Token, Range<usize>)> {
+        let mut tokens: Vec<(Token, Range<usize>)> = Vec::new();
+
+        while let Some(c) = self.peek() {
+            if self.statement_start {
+                if c.is_whitespace() {
+                    self.skip_whitespace();
+                    continue;
+                }
+                // a comment on its own line doesn't start a statement
+                if self.input[self.position..].starts_with("//") {
+                    let start = self.position;
+                    tokens.push((self.read_operator(), start..self.position));
+                    continue;
+                }
+                if c == '#' {
+                    let start = self.position;
+                    tokens.push((self.read_comment(), start..self.position));
+                    continue;
+                }
+                self.statement_start = false;
+                self.command_mode = self.starts_command();
+            }
+
+            let start = self.position;
+            let token = if self.command_mode {
+                self.next_command_token(c)
+            } else {
+                self.next_expression_token(c)
+            };
+
+            if let Some(token) = token {
+                // a line break may jump past here-document bodies, but the token is just `\n`
+                let end = match token.kind {
+                    TokenKind::Punctuation(PunctuationTokenKind::Newline) => start + 1,
+                    _ => self.position,
+                };
+
+                // the word after `<<` is a here-document delimiter
+                let here_doc = match tokens.last() {
+                    Some((
+                        Token {
+                            kind: TokenKind::Operator(OperatorTokenKind::DoubleLess),
+                            value,
+                        },
+                        _,
+                    )) if token.kind == TokenKind::Literal(LiteralTokenKind::Word) => {
+                        Some(value.ends_with('-'))
                     }
+                    _ => None,
+                };
+
+                self.track(&token);
+                if let Some(strip_tabs) = here_doc {
+                    let (body, span) = self.read_here_doc(&token.value, strip_tabs);
+                    tokens.push((token, start..end));
+                    tokens.push((body, span));
+                } else {
+                    tokens.push((token, start..end));
                 }
             }
         }
 
-        tokens.push(Token {
-            kind: TokenKind::EOF,
-            value: "".to_string(),
-        });
+        let end = self.input.len();
+        tokens.push((
+            Token {
+                kind: TokenKind::EOF,
+                value: "".to_string(),
+            },
+            end..end,
+        ));
 
         tokens
     }
@@ -0,0 +1,95 @@
+use crate::ast::Stmt;
+use crate::interpreter::environment::Environment;
+use crate::interpreter::evaluator::{Interpreter, RuntimeError};
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+#[derive(Clone, Debug)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Function(Rc<Function>),
+    NativeFunction(NativeFunction),
+}
+
+pub struct Function {
+    pub name: String,
+    pub params: Vec<String>,
+    pub body: Stmt,
+    pub closure: Rc<RefCell<Environment>>,
+}
+
+pub type NativeFn = fn(&mut Interpreter, &[Value]) -> Result<Value, RuntimeError>;
+
+#[derive(Clone, Copy)]
+pub struct NativeFunction {
+    pub name: &'static str,
+    // None means the function accepts any number of arguments
+    pub arity: Option<usize>,
+    pub func: NativeFn,
+}
+
+impl Value {
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Nil => false,
+            Value::Bool(b) => *b,
+            Value::Number(n) => *n != 0.0,
+            Value::String(s) => !s.is_empty(),
+            Value::Function(_) | Value::NativeFunction(_) => true,
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Bool(_) => "bool",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Function(_) | Value::NativeFunction(_) => "function",
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Function(a), Value::Function(b)) => Rc::ptr_eq(a, b),
+            (Value::NativeFunction(a), Value::NativeFunction(b)) => a.name == b.name,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::String(s) => write!(f, "{}", s),
+            Value::Function(function) => write!(f, "<fun {}>", function.name),
+            Value::NativeFunction(native) => write!(f, "<native fun {}>", native.name),
+        }
+    }
+}
+
+// the closure can point back at the function itself, so only print the signature
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<fun {}({})>", self.name, self.params.join(", "))
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fun {}>", self.name)
+    }
+}
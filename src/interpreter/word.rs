@@ -0,0 +1,312 @@
+use std::fs;
+use std::path::Path;
+
+// one expanded character and whether it came from a quoted (or otherwise literal) context
+type Expanded = Vec<(char, bool)>;
+
+// Expands a raw command word: quote removal, `$NAME`/`${NAME}` substitution, a leading `~`
+// and finally globbing. Unlike POSIX sh, substituted values are never split on whitespace.
+pub fn expand_word(raw: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Vec<String> {
+    let mut expanded: Expanded = Vec::new();
+    let mut quoted = false;
+    let mut chars = raw.chars().peekable();
+
+    if raw == "~" || raw.starts_with("~/") {
+        chars.next();
+        if let Some(home) = lookup("HOME") {
+            expanded.extend(home.chars().map(|c| (c, true)));
+        } else {
+            expanded.push(('~', true));
+        }
+    }
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    expanded.push((escaped, true));
+                }
+            }
+            '\'' => {
+                quoted = true;
+                for c in chars.by_ref() {
+                    if c == '\'' {
+                        break;
+                    }
+                    expanded.push((c, true));
+                }
+            }
+            '"' => {
+                quoted = true;
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => match chars.next() {
+                            Some(escaped @ ('$' | '"' | '\\' | '`')) => {
+                                expanded.push((escaped, true))
+                            }
+                            Some(other) => {
+                                expanded.push(('\\', true));
+                                expanded.push((other, true));
+                            }
+                            None => expanded.push(('\\', true)),
+                        },
+                        '$' => expand_variable(&mut chars, lookup, &mut expanded),
+                        _ => expanded.push((c, true)),
+                    }
+                }
+            }
+            '$' => expand_variable(&mut chars, lookup, &mut expanded),
+            _ => expanded.push((c, false)),
+        }
+    }
+
+    if expanded.is_empty() && !quoted {
+        return Vec::new();
+    }
+
+    let is_pattern = expanded
+        .iter()
+        .any(|(c, literal)| !literal && matches!(c, '*' | '?' | '['));
+    if is_pattern {
+        let pattern: String = expanded
+            .iter()
+            .flat_map(|(c, literal)| {
+                let escape = *literal && matches!(c, '*' | '?' | '[' | '\\');
+                escape.then_some('\\').into_iter().chain(Some(*c))
+            })
+            .collect();
+        let matches = glob(&pattern);
+        if !matches.is_empty() {
+            return matches;
+        }
+    }
+
+    vec![expanded.into_iter().map(|(c, _)| c).collect()]
+}
+
+// Expands the body of a here-document: `$` substitution and backslash escapes of `$`, `\`
+// and `` ` ``, while quotes stay as they are.
+pub fn expand_here_doc(body: &str, lookup: &dyn Fn(&str) -> Option<String>) -> String {
+    let mut expanded: Expanded = Vec::new();
+    let mut chars = body.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.peek() {
+                Some(&escaped @ ('$' | '\\' | '`')) => {
+                    chars.next();
+                    expanded.push((escaped, true));
+                }
+                Some('\n') => {
+                    chars.next();
+                }
+                _ => expanded.push(('\\', true)),
+            },
+            '$' => expand_variable(&mut chars, lookup, &mut expanded),
+            _ => expanded.push((c, true)),
+        }
+    }
+    expanded.into_iter().map(|(c, _)| c).collect()
+}
+
+// characters that end a command, so the next word names a new one
+const COMMAND_SEPARATORS: [char; 4] = ['|', ';', '&', '{'];
+
+// Every word of `line` as raw text with its start offset, quotes kept together, in all of the
+// commands on it.
+pub fn line_words(line: &str) -> Vec<(usize, &str)> {
+    split_commands(line)
+        .into_iter()
+        .flatten()
+        .filter(|(_, word)| !word.is_empty())
+        .collect()
+}
+
+// The raw words of `text` with their start offsets, grouped by the command they belong to.
+// Quoted and escaped characters stay in their word. The last word runs to the end of `text`,
+// empty right after a space.
+pub fn split_commands(text: &str) -> Vec<Vec<(usize, &str)>> {
+    let mut commands = vec![Vec::new()];
+    let mut start = None;
+    let mut quote = None;
+    let mut escaped = false;
+    for (offset, c) in text.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        let words = commands.last_mut().expect("there's always a command");
+        match (c, quote) {
+            (c, Some(open)) if c == open => quote = None,
+            (c, None) if c.is_whitespace() => {
+                if let Some(start) = start.take() {
+                    words.push((start, &text[start..offset]));
+                }
+            }
+            (c, None) if COMMAND_SEPARATORS.contains(&c) => {
+                if let Some(start) = start.take() {
+                    words.push((start, &text[start..offset]));
+                }
+                commands.push(Vec::new());
+            }
+            _ => {
+                start.get_or_insert(offset);
+                match (c, quote) {
+                    ('\\', Some('"') | None) => escaped = true,
+                    ('\'' | '"', None) => quote = Some(c),
+                    _ => {}
+                }
+            }
+        }
+    }
+    let start = start.unwrap_or(text.len());
+    if let Some(words) = commands.last_mut() {
+        words.push((start, &text[start..]));
+    }
+    commands
+}
+
+fn expand_variable(
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+    lookup: &dyn Fn(&str) -> Option<String>,
+    expanded: &mut Expanded,
+) {
+    let mut name = String::new();
+    match chars.peek() {
+        Some('{') => {
+            chars.next();
+            for c in chars.by_ref() {
+                if c == '}' {
+                    break;
+                }
+                name.push(c);
+            }
+        }
+        Some(&c) if matches!(c, '?' | '!' | '#' | '@' | '*') || c.is_ascii_digit() => {
+            chars.next();
+            name.push(c);
+        }
+        _ => {
+            while let Some(&c) = chars.peek() {
+                if !(c.is_ascii_alphanumeric() || c == '_') {
+                    break;
+                }
+                name.push(c);
+                chars.next();
+            }
+        }
+    }
+
+    if name.is_empty() {
+        expanded.push(('$', true));
+        return;
+    }
+
+    if let Some(value) = lookup(&name) {
+        expanded.extend(value.chars().map(|c| (c, true)));
+    }
+}
+
+pub fn glob(pattern: &str) -> Vec<String> {
+    let mut paths = vec![if pattern.starts_with('/') {
+        "/".to_string()
+    } else {
+        String::new()
+    }];
+
+    for component in pattern.split('/').filter(|c| !c.is_empty()) {
+        let mut next = Vec::new();
+        for prefix in &paths {
+            if !component.contains(['*', '?', '[']) {
+                next.push(join_path(prefix, &component.replace('\\', "")));
+                continue;
+            }
+
+            let directory = if prefix.is_empty() { "." } else { prefix };
+            let Ok(entries) = fs::read_dir(directory) else {
+                continue;
+            };
+            let mut names: Vec<String> = entries
+                .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+                // hidden files only match when the pattern asks for them
+                .filter(|name| !name.starts_with('.') || component.starts_with('.'))
+                .filter(|name| glob_match(component, name))
+                .collect();
+            names.sort();
+            next.extend(names.iter().map(|name| join_path(prefix, name)));
+        }
+        paths = next;
+    }
+
+    paths.retain(|path| Path::new(path).symlink_metadata().is_ok());
+    if pattern.ends_with('/') {
+        paths.retain(|path| Path::new(path).is_dir());
+        for path in paths.iter_mut() {
+            path.push('/');
+        }
+    }
+    paths
+}
+
+fn join_path(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else if prefix.ends_with('/') {
+        format!("{}{}", prefix, name)
+    } else {
+        format!("{}/{}", prefix, name)
+    }
+}
+
+// matches `*`, `?`, `[a-z]`/`[!a-z]` classes and `\` escapes against a single name
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    match_from(&pattern, &text)
+}
+
+fn match_from(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') => (0..=text.len()).any(|skip| match_from(&pattern[1..], &text[skip..])),
+        Some('?') => !text.is_empty() && match_from(&pattern[1..], &text[1..]),
+        Some('[') => match match_class(pattern, text.first().copied()) {
+            Some((matched, length)) => matched && match_from(&pattern[length..], &text[1..]),
+            // no closing bracket, so it's just a literal '['
+            None => text.first() == Some(&'[') && match_from(&pattern[1..], &text[1..]),
+        },
+        Some('\\') if pattern.len() > 1 => {
+            text.first() == Some(&pattern[1]) && match_from(&pattern[2..], &text[1..])
+        }
+        Some(c) => text.first() == Some(c) && match_from(&pattern[1..], &text[1..]),
+    }
+}
+
+// returns whether `c` is in the class at the start of `pattern` and the class length
+fn match_class(pattern: &[char], c: Option<char>) -> Option<(bool, usize)> {
+    let mut i = 1;
+    let negated = matches!(pattern.get(i), Some('!' | '^'));
+    if negated {
+        i += 1;
+    }
+
+    let mut matched = false;
+    let mut first = true;
+    while let Some(&start) = pattern.get(i) {
+        if start == ']' && !first {
+            let matched = c.is_some() && matched != negated;
+            return Some((matched, i + 1));
+        }
+        first = false;
+
+        if pattern.get(i + 1) == Some(&'-') && !matches!(pattern.get(i + 2), None | Some(']')) {
+            let end = pattern[i + 2];
+            matched |= c.is_some_and(|c| start <= c && c <= end);
+            i += 3;
+        } else {
+            matched |= c == Some(start);
+            i += 1;
+        }
+    }
+    None
+}
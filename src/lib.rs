@@ -1,4 +1,10 @@
+pub mod banner;
+pub mod editor;
+pub mod history;
 pub mod interpreter;
+pub mod prompt;
+pub mod script;
+pub mod shell;
 pub mod utils;
 
 pub use interpreter::*;
@@ -1,82 +1,266 @@
-use std::io::Write;
-use term_size;
+use sheesh::banner;
+use sheesh::editor::{terminal, LineEditor, ReadLine};
+use sheesh::history::{self, Entry, History};
+use sheesh::interpreter::signals;
+use sheesh::interpreter::*;
+use sheesh::prompt;
+use sheesh::script::{self, ScriptError};
+use sheesh::shell::Shell;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 
 fn main() {
-    println!("Welcome to the sheesh!");
-    let mut message = "Banner for information".to_string();
-    loop {
-        // get terminal width
-        let (width, _) = term_size::dimensions().unwrap_or((80, 24));
-
-        // write a prompt
-        print!(
-            "sheesh $ \n\x1b[1m\x1b[37m\x1b[100m {}{}\x1b[0m",
-            message,
-            " ".repeat(width as usize - message.len() - 1)
-        );
-
-        // move the cursor up a line and 9 characters to the right
-        print!("\x1b[1A\x1b[1000D\x1b[9C");
-        std::io::stdout().flush().expect("TODO: panic message");
-
-        // read a line from the user
-        let mut input = String::new();
-        std::io::stdin()
-            .read_line(&mut input)
-            .expect("TODO: panic message");
-
-        // move the cursor down a line and to the beginning of the line
-        print!("\x1b[1B\x1b[1000D");
-
-        // clear the line
-        print!("\x1b[0K");
-        std::io::stdout().flush().expect("TODO: panic message");
+    // `--norc` skips the config file and `--rcfile path` runs another one instead. The first
+    // other argument names a script to run, and the ones after it are the script's own.
+    let mut norc = false;
+    let mut rcfile = None;
+    let mut script = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--norc" => norc = true,
+            "--rcfile" => match args.next() {
+                Some(path) => rcfile = Some(PathBuf::from(path)),
+                None => {
+                    eprintln!("sosh: --rcfile: file name expected");
+                    std::process::exit(2);
+                }
+            },
+            _ if arg.starts_with('-') => {
+                eprintln!("sosh: {}: unknown option", arg);
+                std::process::exit(2);
+            }
+            _ => {
+                script = Some(
+                    std::iter::once(arg)
+                        .chain(args.by_ref())
+                        .collect::<Vec<_>>(),
+                );
+                break;
+            }
+        }
+    }
 
-        // parse the input
-        let input = input.trim();
+    // lives across prompts so variables and functions survive between lines
+    let mut shell = Shell::new();
+    signals::install();
 
-        // Tokenize the input
-        let tokens: Vec<&str> = input.split_whitespace().collect();
+    // a script runs to the end without job control, prompts or a config file
+    if let Some(arguments) = script {
+        let path = PathBuf::from(&arguments[0]);
+        shell.interpreter.arguments = arguments;
+        std::process::exit(execute_file(&mut shell.interpreter, &path));
+    }
 
-        // dbg!(tokens.clone());
+    // Only lines typed at a terminal are remembered and expanded. Anything else, like a pipe
+    // from another program, gets no greeting, prompt or banner, just the output of what runs,
+    // and leaves the terminal to whoever has it instead of taking it for job control.
+    let interactive = terminal::is_interactive();
+    if interactive {
+        shell.interpreter.jobs.enable_control();
+        println!("Welcome to the sheesh!");
+        if let Some(path) = History::default_path() {
+            let limit = std::env::var("HISTSIZE")
+                .ok()
+                .and_then(|size| size.parse().ok())
+                .unwrap_or(history::DEFAULT_SIZE);
+            *shell.history.borrow_mut() = History::open(path, limit);
+        }
+    }
 
-        // Check if the user wants to exit
-        if tokens.len() == 1 && tokens[0] == "exit" {
-            break;
+    if interactive && !norc {
+        match &rcfile {
+            Some(path) => load_config(&mut shell.interpreter, path, true),
+            None => {
+                if let Some(path) = script::default_config_path() {
+                    load_config(&mut shell.interpreter, &path, false);
+                }
+            }
         }
+    }
+
+    let mut editor = LineEditor::new();
+
+    'prompt: loop {
+        // report background jobs that finished while the last command ran
+        shell.interpreter.notify_jobs();
 
-        // Parse PATH variable
-        if tokens.len() != 0 {
-            let path = std::env::var("PATH");
-            if path.is_ok() {
-                let path = path.unwrap();
-                let paths: Vec<&str> = path.split(":").collect();
-
-                // Check if the command is in the PATH
-                let mut found = false;
-                for p in paths {
-                    let command = format!("{}/{}", p, tokens[0]);
-                    if std::path::Path::new(&command).exists() {
-                        found = true;
-                        let output = std::process::Command::new(command)
-                            .args(&tokens[1..])
-                            .output()
-                            .expect("failed to execute process");
-                        println!("{}", String::from_utf8_lossy(&output.stdout));
-                        break;
+        let (prompt, banner) = if interactive {
+            let banner = banner::render(&mut shell);
+            (prompt::render(&mut shell.interpreter, false), banner)
+        } else {
+            (String::new(), None)
+        };
+
+        // read a line from the user, Ctrl-C throws it away and starts over
+        let mut input = match editor.read_line(&prompt, banner.as_ref(), &mut shell) {
+            Ok(ReadLine::Line(line)) => line,
+            Ok(ReadLine::Interrupted) => {
+                signals::take_interrupt();
+                continue;
+            }
+            // a key bound to a command, which runs without going into the history
+            Ok(ReadLine::Command(command)) => {
+                if let Err(code) = run_script(&mut shell.interpreter, &command) {
+                    std::process::exit(code);
+                }
+                continue;
+            }
+            // end of input, nothing left to run
+            Ok(ReadLine::Eof) => std::process::exit(shell.interpreter.last_status()),
+            Err(error) => {
+                eprintln!("Error: cannot read input: {}", error);
+                std::process::exit(1);
+            }
+        };
+        input.push('\n');
+
+        // keep reading until every here-document has its delimiter and every block is closed
+        while needs_more_input(&input) {
+            let prompt = if interactive {
+                prompt::render(&mut shell.interpreter, true)
+            } else {
+                String::new()
+            };
+            match editor.read_line(&prompt, banner.as_ref(), &mut shell) {
+                Ok(ReadLine::Line(line)) => {
+                    input.push_str(&line);
+                    input.push('\n');
+                }
+                Ok(ReadLine::Interrupted) => {
+                    signals::take_interrupt();
+                    continue 'prompt;
+                }
+                Ok(ReadLine::Command(command)) => {
+                    if let Err(code) = run_script(&mut shell.interpreter, &command) {
+                        std::process::exit(code);
                     }
                 }
+                Ok(ReadLine::Eof) | Err(_) => break,
+            }
+        }
+
+        // a line starting with a space stays out of the history, like in other shells
+        let private = input.starts_with(' ');
 
-                if !found {
-                    println!("Error: command {} not found", tokens[0]);
+        // parse the input
+        let mut input = input.trim().to_string();
+        if input.is_empty() {
+            continue;
+        }
+
+        // `!!` and friends are replaced before anything else sees the line, and the result is
+        // shown so it's clear what runs
+        if interactive {
+            match shell.history.borrow().expand(&input) {
+                Ok(Some(expanded)) => {
+                    println!("{}", expanded);
+                    input = expanded;
+                }
+                Ok(None) => {}
+                Err(error) => {
+                    eprintln!("Error: {}", error);
+                    shell.interpreter.set_last_status(1);
                     continue;
                 }
+            }
+        }
 
-                continue;
-            } else {
-                println!("Error: PATH variable not set");
-                continue;
+        // like expansion, the history is only for lines typed at a terminal
+        let entry = (interactive && !private).then(|| Entry::new(&input));
+        let started = Instant::now();
+        let result = run_script(&mut shell.interpreter, &input);
+        let duration = started.elapsed();
+        shell.interpreter.last_duration = Some(duration);
+        if let Some(mut entry) = entry {
+            entry.status = shell.interpreter.last_status();
+            entry.duration = duration;
+            if let Err(error) = shell.history.borrow_mut().add(entry) {
+                eprintln!("Error: cannot save history: {}", error);
             }
         }
+
+        if let Err(code) = result {
+            std::process::exit(code);
+        }
+    }
+}
+
+// Runs the config file at `path`. Errors are reported with their line and the shell starts
+// anyway, though a missing file is only worth a word when it was asked for by name.
+fn load_config(interpreter: &mut Interpreter, path: &Path, required: bool) {
+    match script::run_file(interpreter, path) {
+        Ok(()) => {}
+        Err(ScriptError::Unreadable(error))
+            if !required && error.kind() == std::io::ErrorKind::NotFound => {}
+        Err(ScriptError::Runtime {
+            error: RuntimeError::Exit(code),
+            ..
+        }) => std::process::exit(code),
+        Err(error) => eprintln!("sosh: {}", error.located(path)),
+    }
+}
+
+// Runs the script at `path` and yields the status to exit with: that of its last command or
+// `exit`, 2 for a syntax error, 1 for another error, and 126 or 127 for a file that can't be
+// read, like other shells.
+fn execute_file(interpreter: &mut Interpreter, path: &Path) -> i32 {
+    let error = match script::run_file(interpreter, path) {
+        Ok(())
+        | Err(ScriptError::Runtime {
+            error: RuntimeError::Interrupted,
+            ..
+        }) => return interpreter.last_status(),
+        Err(ScriptError::Runtime {
+            error: RuntimeError::Exit(code),
+            ..
+        }) => return code,
+        Err(error) => error,
+    };
+    eprintln!("sosh: {}", error.located(path));
+    match error {
+        ScriptError::Unreadable(error) if error.kind() == std::io::ErrorKind::NotFound => 127,
+        ScriptError::Unreadable(_) => 126,
+        ScriptError::Syntax { .. } => 2,
+        ScriptError::Runtime { .. } => 1,
+    }
+}
+
+fn needs_more_input(input: &str) -> bool {
+    let mut tokenizer = Tokenizer::new(input);
+    tokenizer.tokenize();
+    tokenizer.needs_more_input()
+}
+
+// runs one line of input, yielding the exit code if the shell should quit
+fn run_script(interpreter: &mut Interpreter, input: &str) -> Result<(), i32> {
+    let tokens = Tokenizer::new(input).tokenize();
+    let statements = match Parser::new(tokens).parse() {
+        Ok(statements) => statements,
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            interpreter.set_last_status(2);
+            return Ok(());
+        }
+    };
+
+    match interpreter.interpret(&statements) {
+        // echo the value of a bare expression, like any other REPL
+        Ok(value) => {
+            let is_bare_expression = matches!(
+                statements.last(),
+                Some(Stmt::Expr(expr)) if !matches!(expr, Expr::Assign(..))
+            );
+            if is_bare_expression && value != Value::Nil {
+                println!("{}", value);
+            }
+        }
+        Err(RuntimeError::Exit(code)) => return Err(code),
+        Err(RuntimeError::Interrupted) => println!(),
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            interpreter.set_last_status(1);
+        }
     }
+    Ok(())
 }
@@ -0,0 +1,255 @@
+use crate::interpreter::{Interpreter, Value};
+use crate::utils::time;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const DEFAULT_PROMPT: &str = "sheesh $ ";
+pub const DEFAULT_CONTINUATION: &str = "> ";
+
+// What the escapes of a prompt stand for at the moment it's shown.
+#[derive(Clone, Debug, Default)]
+pub struct PromptInfo {
+    pub user: String,
+    pub host: String,
+    pub cwd: String,
+    pub home: Option<String>,
+    // how many trailing directories `\w` keeps, 0 for all of them
+    pub dir_trim: usize,
+    pub status: i32,
+    pub duration: Option<Duration>,
+    pub jobs: usize,
+    // the branch checked out in the git repository around `cwd`
+    pub git_branch: Option<String>,
+    pub timestamp: u64,
+    pub root: bool,
+}
+
+impl PromptInfo {
+    pub fn gather(interpreter: &Interpreter) -> Self {
+        // SAFETY: getuid can't fail
+        let uid = unsafe { libc::getuid() };
+        let cwd = std::env::current_dir().unwrap_or_default();
+        PromptInfo {
+            user: interpreter
+                .lookup_variable("USER")
+                .unwrap_or_else(|| user_name(uid)),
+            host: host_name(),
+            cwd: cwd.display().to_string(),
+            home: interpreter.lookup_variable("HOME"),
+            dir_trim: interpreter
+                .lookup_variable("PROMPT_DIRTRIM")
+                .and_then(|trim| trim.parse().ok())
+                .unwrap_or(0),
+            status: interpreter.last_status(),
+            duration: interpreter.last_duration,
+            jobs: interpreter.jobs.len(),
+            git_branch: git_branch(&cwd),
+            timestamp: time::now(),
+            root: uid == 0,
+        }
+    }
+}
+
+// The prompt to show: what a sosh function `prompt` returns when there is one, otherwise
+// $PROMPT, with its escapes expanded either way. `continuation` picks $PROMPT2 instead, for
+// the lines a command goes on over.
+pub fn render(interpreter: &mut Interpreter, continuation: bool) -> String {
+    let (variable, default) = if continuation {
+        ("PROMPT2", DEFAULT_CONTINUATION)
+    } else {
+        ("PROMPT", DEFAULT_PROMPT)
+    };
+
+    let mut template = None;
+    if !continuation {
+        if let Some(function @ Value::Function(_)) = interpreter.get("prompt") {
+            // the function may run commands, which mustn't count as the last one
+            let status = interpreter.last_status;
+            match interpreter.call(&function, Vec::new()) {
+                Ok(Value::String(text)) => template = Some(text),
+                Ok(value) => template = Some(value.to_string()),
+                Err(error) => eprintln!("Error: prompt: {}", error),
+            }
+            interpreter.last_status = status;
+        }
+    }
+    let template = template
+        .or_else(|| interpreter.lookup_variable(variable))
+        .unwrap_or_else(|| default.to_string());
+    expand(&template, &PromptInfo::gather(interpreter))
+}
+
+// Expands the escapes in a prompt:
+//
+//   \u  user name            \h  host name up to the first dot, \H all of it
+//   \w  working directory, `~` for home and only $PROMPT_DIRTRIM trailing directories if set
+//   \W  last directory of it \?  exit status of the last command
+//   \D  how long it ran      \t  time as 14:05:09, \T as 14:05
+//   \j  number of jobs       \$  `#` for root, `$` for everyone else
+//   \g  git branch, or the commit when none is checked out
+//   \n  line break           \\  a backslash, \e the escape character
+//   \c{colour}  red, green, bright-blue, bold, underline, reset..., or SGR codes like 1;35
+//
+// Anything else is kept as written.
+pub fn expand(template: &str, info: &PromptInfo) -> String {
+    let mut out = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        let Some(escape) = chars.next() else {
+            out.push('\\');
+            break;
+        };
+        match escape {
+            'u' => out.push_str(&info.user),
+            'h' => out.push_str(info.host.split('.').next().unwrap_or_default()),
+            'H' => out.push_str(&info.host),
+            'w' => out.push_str(&short_directory(
+                &info.cwd,
+                info.home.as_deref(),
+                info.dir_trim,
+            )),
+            'W' => {
+                let cwd = short_directory(&info.cwd, info.home.as_deref(), 0);
+                let last = match cwd.as_str() {
+                    "/" | "~" => &cwd,
+                    _ => cwd.rsplit('/').next().unwrap_or_default(),
+                };
+                out.push_str(last);
+            }
+            '?' => out.push_str(&info.status.to_string()),
+            'D' => out.push_str(&info.duration.map(time::format_duration).unwrap_or_default()),
+            't' => out.push_str(&time::format_timestamp(info.timestamp, "%H:%M:%S")),
+            'T' => out.push_str(&time::format_timestamp(info.timestamp, "%H:%M")),
+            'j' => out.push_str(&info.jobs.to_string()),
+            'g' => out.push_str(info.git_branch.as_deref().unwrap_or_default()),
+            '$' => out.push(if info.root { '#' } else { '$' }),
+            'n' => out.push('\n'),
+            'e' => out.push('\x1b'),
+            '\\' => out.push('\\'),
+            'c' if chars.peek() == Some(&'{') => {
+                chars.next();
+                let name: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                match colour(&name) {
+                    Some(code) => out.push_str(&format!("\x1b[{}m", code)),
+                    None => out.push_str(&format!("\\c{{{}}}", name)),
+                }
+            }
+            _ => {
+                out.push('\\');
+                out.push(escape);
+            }
+        }
+    }
+    out
+}
+
+// SGR parameters for a colour or style name, or parameters given as they are
+fn colour(name: &str) -> Option<String> {
+    const COLOURS: [&str; 8] = [
+        "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+    ];
+    let code = match name {
+        "reset" => 0,
+        "bold" => 1,
+        "dim" => 2,
+        "italic" => 3,
+        "underline" => 4,
+        _ => {
+            if !name.is_empty() && name.chars().all(|c| c.is_ascii_digit() || c == ';') {
+                return Some(name.to_string());
+            }
+            let (base, bright) = match name.strip_prefix("bright-") {
+                Some(base) => (base, true),
+                None => (name, false),
+            };
+            let index = COLOURS.iter().position(|&colour| colour == base)?;
+            index + if bright { 90 } else { 30 }
+        }
+    };
+    Some(code.to_string())
+}
+
+// `cwd` with `~` for the home directory, and when `trim` is set only that many trailing
+// directories after a `…`.
+pub fn short_directory(cwd: &str, home: Option<&str>, trim: usize) -> String {
+    let (root, rest) = match home.filter(|home| !home.is_empty() && *home != "/") {
+        Some(home) if cwd == home => return "~".to_string(),
+        Some(home)
+            if cwd
+                .strip_prefix(home)
+                .is_some_and(|rest| rest.starts_with('/')) =>
+        {
+            ("~/", &cwd[home.len() + 1..])
+        }
+        _ => ("/", cwd.trim_start_matches('/')),
+    };
+    let components: Vec<&str> = rest.split('/').filter(|part| !part.is_empty()).collect();
+    if trim > 0 && components.len() > trim {
+        format!(
+            "{}…/{}",
+            root,
+            components[components.len() - trim..].join("/")
+        )
+    } else {
+        format!("{}{}", root, components.join("/"))
+    }
+}
+
+// The branch checked out in the repository `dir` is in, read from `.git/HEAD` of the nearest
+// directory up that has one. A detached HEAD gives the start of its commit instead.
+pub fn git_branch(dir: &Path) -> Option<String> {
+    let dot_git = dir
+        .ancestors()
+        .map(|dir| dir.join(".git"))
+        .find(|path| path.exists())?;
+    // worktrees and submodules have a file pointing at the real git directory
+    let git_dir = if dot_git.is_file() {
+        let link = std::fs::read_to_string(&dot_git).ok()?;
+        let target = PathBuf::from(link.strip_prefix("gitdir:")?.trim());
+        dot_git.parent()?.join(target)
+    } else {
+        dot_git
+    };
+    let head = std::fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    match head.strip_prefix("ref:") {
+        Some(reference) => {
+            let reference = reference.trim();
+            Some(
+                reference
+                    .strip_prefix("refs/heads/")
+                    .unwrap_or(reference)
+                    .to_string(),
+            )
+        }
+        None => Some(head.chars().take(7).collect()),
+    }
+}
+
+fn user_name(uid: libc::uid_t) -> String {
+    // SAFETY: getpwuid returns null or a pointer to a static record that stays valid until
+    // the next call, and the name is copied out right away
+    unsafe {
+        let passwd = libc::getpwuid(uid);
+        if passwd.is_null() {
+            return uid.to_string();
+        }
+        std::ffi::CStr::from_ptr((*passwd).pw_name)
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+fn host_name() -> String {
+    let mut buffer = [0u8; 256];
+    // SAFETY: gethostname writes at most `buffer.len()` bytes
+    if unsafe { libc::gethostname(buffer.as_mut_ptr().cast(), buffer.len()) } != 0 {
+        return String::new();
+    }
+    let length = buffer.iter().position(|&b| b == 0).unwrap_or(buffer.len());
+    String::from_utf8_lossy(&buffer[..length]).into_owned()
+}
@@ -0,0 +1,86 @@
+use crate::interpreter::{Interpreter, Parser, RuntimeError, Tokenizer};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+// Why running a file of sosh stopped, with the line to blame.
+#[derive(Debug)]
+pub enum ScriptError {
+    Unreadable(std::io::Error),
+    // a syntax error, found before anything ran
+    Syntax { line: usize, message: String },
+    // an error raised by the statement starting on `line`, `exit` included
+    Runtime { line: usize, error: RuntimeError },
+}
+
+impl ScriptError {
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            ScriptError::Unreadable(_) => None,
+            ScriptError::Syntax { line, .. } | ScriptError::Runtime { line, .. } => Some(*line),
+        }
+    }
+
+    // the error as `path:line: message`
+    pub fn located(&self, path: &Path) -> String {
+        match self.line() {
+            Some(line) => format!("{}:{}: {}", path.display(), line, self),
+            None => format!("{}: {}", path.display(), self),
+        }
+    }
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScriptError::Unreadable(error) => write!(f, "{}", error),
+            ScriptError::Syntax { message, .. } => write!(f, "{}", message),
+            ScriptError::Runtime { error, .. } => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+// Parses the whole of `source` and runs it a statement at a time. Nothing runs when it
+// doesn't parse, and the first runtime error stops it.
+pub fn run_source(interpreter: &mut Interpreter, source: &str) -> Result<(), ScriptError> {
+    let tokens = Tokenizer::new(source).tokenize_with_spans();
+    let line_of = |index: usize| {
+        let offset = tokens
+            .get(index)
+            .map_or(source.len(), |(_, span)| span.start);
+        source[..offset].matches('\n').count() + 1
+    };
+
+    let mut parser = Parser::new(tokens.iter().map(|(token, _)| token.clone()).collect());
+    let statements = parser
+        .parse_located()
+        .map_err(|message| ScriptError::Syntax {
+            line: line_of(parser.position()),
+            message,
+        })?;
+
+    for (start, statement) in statements {
+        interpreter
+            .interpret(std::slice::from_ref(&statement))
+            .map_err(|error| ScriptError::Runtime {
+                line: line_of(start),
+                error,
+            })?;
+    }
+    Ok(())
+}
+
+pub fn run_file(interpreter: &mut Interpreter, path: &Path) -> Result<(), ScriptError> {
+    let source = std::fs::read_to_string(path).map_err(ScriptError::Unreadable)?;
+    run_source(interpreter, &source)
+}
+
+// `$XDG_CONFIG_HOME/sosh/config.sosh`, which an interactive shell runs before the first prompt
+pub fn default_config_path() -> Option<PathBuf> {
+    let config = match std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(config) => PathBuf::from(config),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(config.join("sosh").join("config.sosh"))
+}
@@ -0,0 +1,341 @@
+use crate::banner::Banner;
+use crate::editor::completion::{Completer, FunctionCompleter};
+use crate::editor::keymap::{self, Action, Binding, Keymap};
+use crate::history::History;
+use crate::interpreter::builtins::quote;
+use crate::interpreter::{Interpreter, RuntimeError, Value};
+use crate::utils::time;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+// argument completion for commands, by command name
+pub type Completers = BTreeMap<String, Rc<dyn Completer>>;
+
+// The interpreter with what the interactive shell keeps around it: the history, the keymaps,
+// the completers and the banner, none of which the language knows about. The builtins that
+// change them are added to the interpreter with a handle on the same tables, so they're shared
+// and only borrowed for as long as one thing looks at them.
+pub struct Shell {
+    pub interpreter: Interpreter,
+    pub history: Rc<RefCell<History>>,
+    // what keys do in the line editor, by keymap name
+    pub keymaps: Rc<RefCell<BTreeMap<String, Keymap>>>,
+    pub completers: Rc<RefCell<Completers>>,
+    // what the bar under the line being typed shows, set with `banner`
+    pub banner: Rc<RefCell<Banner>>,
+}
+
+impl Default for Shell {
+    fn default() -> Self {
+        Shell::new()
+    }
+}
+
+impl Shell {
+    pub fn new() -> Self {
+        let mut shell = Shell {
+            interpreter: Interpreter::new(),
+            history: Rc::default(),
+            keymaps: Rc::new(RefCell::new(keymap::defaults())),
+            completers: Rc::default(),
+            banner: Rc::default(),
+        };
+
+        let history_table = shell.history.clone();
+        shell.interpreter.define_builtin("history", move |_, args| {
+            history(&mut history_table.borrow_mut(), args)
+        });
+        let completers = shell.completers.clone();
+        shell
+            .interpreter
+            .define_builtin("complete", move |interpreter, args| {
+                complete(interpreter, &mut completers.borrow_mut(), args)
+            });
+        let keymaps = shell.keymaps.clone();
+        shell
+            .interpreter
+            .define_builtin("bind", move |interpreter, args| {
+                bind(interpreter, &mut keymaps.borrow_mut(), args)
+            });
+        let banner_segments = shell.banner.clone();
+        shell.interpreter.define_builtin("banner", move |_, args| {
+            banner(&mut banner_segments.borrow_mut(), args)
+        });
+
+        shell
+    }
+}
+
+// `history [-v] [count]` lists entries, `-v` with when, where, how long and how it ended;
+// `history -d n` deletes entry n and `history -c` all of them. `history forget [n]` drops the
+// last n entries from the file too, and `history redact`/`history ignore` list or add the
+// patterns that keep secrets out of it, with `-c` clearing them first.
+fn history(history: &mut History, args: &[String]) -> Result<i32, RuntimeError> {
+    match args.first().map(String::as_str) {
+        Some("-c") => {
+            if let Err(error) = history.clear() {
+                eprintln!("history: {}", error);
+                return Ok(1);
+            }
+            return Ok(0);
+        }
+        Some("-d") => {
+            let Some(number) = args.get(1).and_then(|number| number.parse().ok()) else {
+                eprintln!("history: -d: expected an entry number");
+                return Ok(2);
+            };
+            if let Err(error) = history.remove(number) {
+                eprintln!("history: {}", error);
+                return Ok(1);
+            }
+            return Ok(0);
+        }
+        Some("forget") => {
+            let Some(count) = args.get(1).map_or(Some(1), |count| count.parse().ok()) else {
+                eprintln!("history: forget: {}: expected a count", args[1]);
+                return Ok(2);
+            };
+            if let Err(error) = history.forget(count) {
+                eprintln!("history: {}", error);
+                return Ok(1);
+            }
+            return Ok(0);
+        }
+        Some(list @ ("redact" | "ignore")) => {
+            let redactions = &mut history.redactions;
+            let patterns = if list == "redact" {
+                &mut redactions.redact
+            } else {
+                &mut redactions.ignore
+            };
+            let mut new = &args[1..];
+            if new.first().is_some_and(|arg| arg == "-c") {
+                patterns.clear();
+                new = &new[1..];
+            } else if new.is_empty() {
+                for pattern in patterns.iter() {
+                    println!("{}", pattern);
+                }
+            }
+            patterns.extend(new.iter().cloned());
+            return Ok(0);
+        }
+        _ => {}
+    }
+
+    let verbose = args.first().is_some_and(|arg| arg == "-v");
+    let count = match args.get(usize::from(verbose)) {
+        None => history.len(),
+        Some(count) => match count.parse::<usize>() {
+            Ok(count) => count,
+            Err(_) => {
+                eprintln!("history: {}: invalid count", count);
+                return Ok(2);
+            }
+        },
+    };
+
+    let start = history.len().saturating_sub(count);
+    for (index, entry) in history.entries().iter().enumerate().skip(start) {
+        if verbose {
+            println!(
+                "{:>5}  {}  {:>3}  {:>7}  {}  {}",
+                index + 1,
+                time::format_timestamp(entry.timestamp, "%Y-%m-%d %H:%M:%S"),
+                entry.status,
+                time::format_duration(entry.duration),
+                entry.cwd,
+                entry.command
+            );
+        } else {
+            println!("{:>5}  {}", index + 1, entry.command);
+        }
+    }
+    Ok(0)
+}
+
+// `complete -F fun command...` completes the arguments of the commands with the sosh function
+// `fun`, `complete -r command...` goes back to file names, and `complete` alone lists them.
+fn complete(
+    interpreter: &Interpreter,
+    completers: &mut Completers,
+    args: &[String],
+) -> Result<i32, RuntimeError> {
+    match args.first().map(String::as_str) {
+        None | Some("-p") => {
+            for (command, completer) in completers.iter() {
+                println!("complete -F {} {}", completer.describe(), command);
+            }
+            Ok(0)
+        }
+        Some("-F") => {
+            let (Some(name), true) = (args.get(1), args.len() > 2) else {
+                eprintln!("complete: usage: complete -F function command...");
+                return Ok(2);
+            };
+            let function @ Value::Function(_) = interpreter.get(name).unwrap_or(Value::Nil) else {
+                eprintln!("complete: {}: not a function", name);
+                return Ok(1);
+            };
+            for command in &args[2..] {
+                completers.insert(
+                    command.to_string(),
+                    Rc::new(FunctionCompleter(function.clone())),
+                );
+            }
+            Ok(0)
+        }
+        Some("-r") => {
+            let mut status = 0;
+            for command in &args[1..] {
+                if completers.remove(command).is_none() {
+                    eprintln!("complete: {}: no completion defined", command);
+                    status = 1;
+                }
+            }
+            Ok(status)
+        }
+        Some(flag) => {
+            eprintln!(
+                "complete: {}: unsupported flag, expected -F, -r or -p",
+                flag
+            );
+            Ok(2)
+        }
+    }
+}
+
+// `bind keys action` and `bind -x keys command` bind a key sequence like `C-x C-e` in the
+// keymap typing uses, emacs or vi-insert, or the one `-m` names, `bind -r keys` removes it,
+// `bind -l` lists the actions and `bind` alone or `bind -p` lists the bindings.
+fn bind(
+    interpreter: &Interpreter,
+    keymaps: &mut BTreeMap<String, Keymap>,
+    args: &[String],
+) -> Result<i32, RuntimeError> {
+    let mut args = args;
+    let mut name = if interpreter.options.vi {
+        keymap::VI_INSERT
+    } else {
+        keymap::EMACS
+    };
+    if args.first().is_some_and(|arg| arg == "-m") {
+        let Some(map) = args.get(1) else {
+            eprintln!("bind: -m: keymap name expected");
+            return Ok(2);
+        };
+        name = map;
+        args = &args[2..];
+    }
+    let Some(keymap) = keymaps.get_mut(name) else {
+        eprintln!("bind: {}: unknown keymap", name);
+        return Ok(1);
+    };
+
+    let keys = |spec: &str| match keymap::parse_keys(spec) {
+        Ok(keys) => Some(keys),
+        Err(error) => {
+            eprintln!("bind: {}", error);
+            None
+        }
+    };
+    let strings: Vec<&str> = args.iter().map(String::as_str).collect();
+    match strings[..] {
+        [] | ["-p"] => {
+            for (keys, binding) in keymap.bindings() {
+                match binding {
+                    Binding::Action(action) => println!("bind {} {}", quote(&keys), action.name()),
+                    Binding::Command(command) => {
+                        println!("bind -x {} {}", quote(&keys), quote(command))
+                    }
+                }
+            }
+            Ok(0)
+        }
+        ["-l"] => {
+            for name in Action::names() {
+                println!("{}", name);
+            }
+            Ok(0)
+        }
+        ["-r", spec] => {
+            let Some(keys) = keys(spec) else {
+                return Ok(1);
+            };
+            if keymap.unbind(&keys).is_none() {
+                eprintln!("bind: {}: not bound", spec);
+                return Ok(1);
+            }
+            Ok(0)
+        }
+        ["-x", spec, command] => {
+            let Some(keys) = keys(spec) else {
+                return Ok(1);
+            };
+            keymap.bind(keys, Binding::Command(command.to_string()));
+            Ok(0)
+        }
+        [spec, action] if !spec.starts_with('-') || spec == "-" => {
+            let Some(action) = Action::from_name(action) else {
+                eprintln!("bind: {}: unknown action, see bind -l", action);
+                return Ok(1);
+            };
+            let Some(keys) = keys(spec) else {
+                return Ok(1);
+            };
+            keymap.bind(keys, Binding::Action(action));
+            Ok(0)
+        }
+        _ => {
+            eprintln!(
+                "bind: usage: bind [-m keymap] [-l | -p | -r keys | -x keys command | keys action]"
+            );
+            Ok(2)
+        }
+    }
+}
+
+// `banner -l text`, `-c text` and `-r text` set what the left, middle and right of the banner
+// show, written like a prompt, `banner -d` puts the defaults back and `banner` alone prints the
+// command that sets what it shows now.
+fn banner(banner: &mut Banner, args: &[String]) -> Result<i32, RuntimeError> {
+    let usage = || {
+        eprintln!("banner: usage: banner [-d | [-l text] [-c text] [-r text]]");
+        Ok(2)
+    };
+    match args {
+        [] => {
+            println!(
+                "banner -l {} -c {} -r {}",
+                quote(&banner.left),
+                quote(&banner.center),
+                quote(&banner.right)
+            );
+            return Ok(0);
+        }
+        [flag] if flag == "-d" => {
+            *banner = Banner::default();
+            return Ok(0);
+        }
+        _ => {}
+    }
+
+    // nothing changes unless all of it is right
+    let mut changed = banner.clone();
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        let segment = match flag.as_str() {
+            "-l" => &mut changed.left,
+            "-c" => &mut changed.center,
+            "-r" => &mut changed.right,
+            _ => return usage(),
+        };
+        let Some(text) = args.next() else {
+            return usage();
+        };
+        *segment = text.clone();
+    }
+    *banner = changed;
+    Ok(0)
+}
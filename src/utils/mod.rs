@@ -1,3 +1,4 @@
+pub mod time;
 pub mod traits;
 
 pub use traits::IteratorExt;
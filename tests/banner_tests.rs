@@ -0,0 +1,120 @@
+use sheesh::banner::{self, Banner};
+use sheesh::editor::width;
+use sheesh::interpreter::*;
+use sheesh::prompt::git_branch;
+use sheesh::shell::Shell;
+
+const STYLE: &str = "\x1b[1m\x1b[37m\x1b[100m";
+
+fn run_in(interpreter: &mut Interpreter, source: &str) {
+    let statements = Parser::new(Tokenizer::new(source).tokenize())
+        .parse()
+        .expect("source should parse");
+    interpreter.interpret(&statements).unwrap();
+}
+
+fn segments(left: &str, center: &str, right: &str) -> Banner {
+    Banner {
+        left: left.to_string(),
+        center: center.to_string(),
+        right: right.to_string(),
+    }
+}
+
+// the text of a laid out banner without its colours
+fn plain(text: &str) -> String {
+    width::visible_chars(text).collect()
+}
+
+#[test]
+fn test_banner_layout() {
+    let banner = segments("[0]", "main", "12:00");
+    assert_eq!(plain(&banner.layout(24)), " [0]     main    12:00 ");
+    assert_eq!(plain(&banner.layout(30)), " [0]        main       12:00 ");
+    // the middle goes first, then the right is cut short
+    assert_eq!(plain(&banner.layout(14)), " [0]   12:00 ");
+    assert_eq!(plain(&banner.layout(8)), " [0] 1 ");
+    assert_eq!(plain(&banner.layout(5)), " [0 ");
+    assert_eq!(banner.layout(2), "");
+
+    // every row leaves its last column empty, however wide the text
+    let wide = segments("日本語のテキスト", "", "右");
+    for columns in 3..30 {
+        assert_eq!(width::display_width(&wide.layout(columns)), columns - 1);
+    }
+    assert_eq!(plain(&wide.layout(12)), " 日本語の  ");
+
+    // colours in segments don't leak into the rest of the bar
+    let coloured = segments("\x1b[31mx", "", "");
+    assert_eq!(
+        coloured.layout(6),
+        format!("{} \x1b[31mx{}   \x1b[0m", STYLE, STYLE)
+    );
+}
+
+#[test]
+fn test_banner_builtin() {
+    let mut shell = Shell::new();
+    assert_eq!(*shell.banner.borrow(), Banner::default());
+
+    run_in(&mut shell.interpreter, r"banner -l '\?' -r 'right'");
+    assert_eq!(*shell.banner.borrow(), segments(r"\?", r"\g", "right"));
+    run_in(&mut shell.interpreter, "banner -c ''");
+    assert_eq!(shell.banner.borrow().center, "");
+
+    // a bad flag leaves everything as it was
+    run_in(&mut shell.interpreter, "banner -l changed -x oops");
+    assert_eq!(shell.interpreter.last_status(), 2);
+    assert_eq!(shell.banner.borrow().left, r"\?");
+    run_in(&mut shell.interpreter, "banner -r");
+    assert_eq!(shell.interpreter.last_status(), 2);
+
+    run_in(&mut shell.interpreter, "banner -d");
+    assert_eq!(*shell.banner.borrow(), Banner::default());
+}
+
+#[test]
+fn test_banner_hook() {
+    let mut shell = Shell::new();
+    run_in(
+        &mut shell.interpreter,
+        "fun update_banner() { banner -l 'hook' -c '' -r '\\j'; false; }",
+    );
+    let shown = banner::render(&mut shell).expect("the banner is on by default");
+    assert_eq!(shown, segments("hook", "", "0"));
+    assert_eq!(shell.interpreter.last_status(), 0);
+
+    run_in(&mut shell.interpreter, "set +o banner");
+    assert_eq!(banner::render(&mut shell), None);
+    run_in(&mut shell.interpreter, "set -o banner");
+    assert!(banner::render(&mut shell).is_some());
+}
+
+#[test]
+fn test_git_branch() {
+    let root = std::env::temp_dir().join(format!("sosh-git-{}", std::process::id()));
+    let nested = root.join("src").join("deep");
+    std::fs::create_dir_all(root.join(".git")).unwrap();
+    std::fs::create_dir_all(&nested).unwrap();
+
+    std::fs::write(root.join(".git/HEAD"), "ref: refs/heads/feature/banner\n").unwrap();
+    assert_eq!(git_branch(&root).as_deref(), Some("feature/banner"));
+    assert_eq!(git_branch(&nested).as_deref(), Some("feature/banner"));
+
+    std::fs::write(
+        root.join(".git/HEAD"),
+        "93f596d2c1e0f5a1b0c9d8e7f6a5b4c3d2e1f0a9\n",
+    )
+    .unwrap();
+    assert_eq!(git_branch(&nested).as_deref(), Some("93f596d"));
+
+    // a worktree, whose .git is a file naming the real one
+    let worktree = root.join("worktree");
+    std::fs::create_dir_all(root.join(".git/worktrees/wt")).unwrap();
+    std::fs::create_dir_all(&worktree).unwrap();
+    std::fs::write(root.join(".git/worktrees/wt/HEAD"), "ref: refs/heads/wt\n").unwrap();
+    std::fs::write(worktree.join(".git"), "gitdir: ../.git/worktrees/wt\n").unwrap();
+    assert_eq!(git_branch(&worktree).as_deref(), Some("wt"));
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
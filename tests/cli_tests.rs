@@ -0,0 +1,134 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+// runs sosh with `input` piped to it, the way other programs drive it
+fn run_piped(input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_sosh"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("sosh should start");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_piped_input() {
+    // nothing but what the commands print, and the status of the last one
+    let output = run_piped("echo one\nlet x = 2\necho two $x\nfalse\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "one\ntwo 2\n");
+    assert!(output.stderr.is_empty());
+    assert_eq!(output.status.code(), Some(1));
+
+    // piped lines aren't kept in the history
+    let output = run_piped("echo one\nhistory\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "one\n");
+
+    let output = run_piped("cat <<END\nbody\nEND\nexit 3\necho unreachable\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "body\n");
+    assert_eq!(output.status.code(), Some(3));
+
+    // errors go to stderr and fail the line
+    let output = run_piped("print(missing)\n");
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("missing"));
+    assert_eq!(output.status.code(), Some(1));
+    let output = run_piped("echo fine\nlet = \n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "fine\n");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_piped_blocks_span_lines() {
+    // a block runs once it's closed, not line by line
+    let output = run_piped("let i = 0\nwhile (i < 2) {\n  echo $i\n  i += 1\n}\necho end\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "0\n1\nend\n");
+    assert!(output.stderr.is_empty());
+    assert_eq!(output.status.code(), Some(0));
+
+    // and so does a header with its body on the next line
+    let output = run_piped(
+        "let i = 0\nwhile (i < 2)\n{ echo $i; i += 1 }\nif (i > 5) {\n} else\necho else\n",
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "0\n1\nelse\n");
+    assert_eq!(output.status.code(), Some(0));
+
+    // a broken line is an error of its own, and the next one runs
+    let output = run_piped("let x = 1 +\necho next\nlet\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "next\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr)
+            .matches("Error")
+            .count(),
+        2
+    );
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_runaway_recursion_is_an_error() {
+    // the shell reports it and goes on with the next line instead of overflowing its stack
+    let output = run_piped(
+        "fun f() { return f() }\nf()\nfun g(n) { if (n == 0) { return 0 } return g(n - 1) }\ng(100)\necho still here\n",
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "0\nstill here\n");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("maximum recursion depth exceeded"));
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn test_script_files() {
+    let dir = std::env::temp_dir().join(format!("sosh-scripts-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let script = dir.join("greet.sosh");
+    std::fs::write(
+        &script,
+        "#!/usr/bin/env sosh\n# says hello\necho \"$# for $0:\" $1 \"$2\" # trailing\nexit 5\n",
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_sosh"))
+        .arg(&script)
+        .args(["sopy", "and -x"])
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("2 for {}: sopy and -x\n", script.display())
+    );
+    assert_eq!(output.status.code(), Some(5));
+
+    // errors name the file and line, and set the status like other shells do
+    let broken = dir.join("broken.sosh");
+    std::fs::write(&broken, "echo ran\nprint(nope)\necho skipped\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_sosh"))
+        .arg(&broken)
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ran\n");
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .starts_with(&format!("sosh: {}:2: ", broken.display())));
+    assert_eq!(output.status.code(), Some(1));
+
+    std::fs::write(&broken, "echo never\nif (\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_sosh"))
+        .arg(&broken)
+        .output()
+        .unwrap();
+    assert!(output.stdout.is_empty());
+    assert_eq!(output.status.code(), Some(2));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_sosh"))
+        .arg(dir.join("missing.sosh"))
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(127));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
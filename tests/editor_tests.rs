@@ -0,0 +1,541 @@
+use sheesh::editor::completion::{
+    command_words, common_prefix, current_word, menu_rows, path_candidates, quote, unquote,
+    CompletionContext,
+};
+use sheesh::editor::highlight::{highlight, Theme};
+use sheesh::editor::keymap::{format_keys, parse_keys, Lookup};
+use sheesh::editor::keys::decode;
+use sheesh::editor::search::find_match;
+use sheesh::editor::vi::{self, Command, Mode, Motion, Operator, Parse, Vi};
+use sheesh::editor::width::{advance, display_width, truncate};
+use sheesh::editor::{Action, Binding, Key, Keymap, LineBuffer, Search, SearchMode};
+use sheesh::history::Entry;
+use sheesh::interpreter::{Interpreter, Parser, Tokenizer};
+use sheesh::shell::Shell;
+
+fn key(bytes: &[u8]) -> Key {
+    let mut rest = bytes[1..].iter().copied();
+    decode(bytes[0], &mut rest)
+}
+
+#[test]
+fn test_decode_keys() {
+    assert_eq!(key(b"a"), Key::Char('a'));
+    assert_eq!(key("é".as_bytes()), Key::Char('é'));
+    assert_eq!(key(b"\r"), Key::Enter);
+    // Ctrl-J is a key of its own, so it can be bound apart from Enter
+    assert_eq!(key(b"\n"), Key::Ctrl('j'));
+    assert_eq!(key(b"\x01"), Key::Ctrl('a'));
+    assert_eq!(key(b"\x7f"), Key::Backspace);
+    assert_eq!(key(b"\x1b"), Key::Escape);
+    assert_eq!(key(b"\x1bb"), Key::Alt('b'));
+    assert_eq!(key(b"\x1b\x7f"), Key::AltBackspace);
+    assert_eq!(key(b"\x1b[D"), Key::Left);
+    assert_eq!(key(b"\x1b[1;5C"), Key::CtrlRight);
+    assert_eq!(key(b"\x1bOH"), Key::Home);
+    assert_eq!(key(b"\x1b[4~"), Key::End);
+    assert_eq!(key(b"\x1b[3~"), Key::Delete);
+}
+
+#[test]
+fn test_display_width() {
+    assert_eq!(display_width("sheesh $ "), 9);
+    assert_eq!(display_width("\x1b[1;32mok\x1b[0m"), 2);
+    assert_eq!(display_width("日本"), 4);
+    assert_eq!(display_width("e\u{301}"), 1);
+}
+
+#[test]
+fn test_advance_wraps_like_a_terminal() {
+    assert_eq!(advance("abcd", 4, (0, 0)), (0, 4));
+    assert_eq!(advance("abcde", 4, (0, 0)), (1, 1));
+    // a wide character that doesn't fit moves to the next row whole
+    assert_eq!(advance("abc日", 4, (0, 0)), (1, 2));
+    assert_eq!(advance("ab\ncd", 10, (0, 3)), (1, 2));
+}
+
+#[test]
+fn test_cursor_motion() {
+    let mut buffer = LineBuffer::new("echo e\u{301}t");
+    buffer.move_left();
+    assert_eq!(buffer.before_cursor(), "echo e\u{301}");
+    // combining marks stay with the character before them
+    buffer.move_left();
+    assert_eq!(buffer.before_cursor(), "echo ");
+    buffer.move_right();
+    assert_eq!(buffer.before_cursor(), "echo e\u{301}");
+
+    // but line breaks and tabs from multi-line entries are characters of their own
+    let mut buffer = LineBuffer::new("cat <<END\n\tx\nEND");
+    buffer.move_left();
+    buffer.move_left();
+    buffer.move_left();
+    buffer.move_left();
+    assert_eq!(buffer.before_cursor(), "cat <<END\n\tx");
+    buffer.move_left();
+    assert_eq!(buffer.before_cursor(), "cat <<END\n\t");
+    buffer.move_left();
+    assert_eq!(buffer.before_cursor(), "cat <<END\n");
+    assert_eq!(buffer.delete_before(), "\n");
+    assert_eq!(buffer.text(), "cat <<END\tx\nEND");
+
+    let mut buffer = LineBuffer::new("git commit --amend");
+    buffer.move_word_left();
+    assert_eq!(buffer.before_cursor(), "git commit --");
+    buffer.move_word_left();
+    assert_eq!(buffer.before_cursor(), "git ");
+    buffer.move_word_right();
+    assert_eq!(buffer.before_cursor(), "git commit");
+    buffer.move_home();
+    assert_eq!(buffer.cursor(), 0);
+    buffer.move_end();
+    assert_eq!(buffer.cursor(), buffer.text().len());
+}
+
+#[test]
+fn test_editing() {
+    let mut buffer = LineBuffer::new("ls /usr/lo");
+    assert_eq!(buffer.delete_word_before(), "/usr/lo");
+    assert_eq!(buffer.text(), "ls ");
+
+    let mut buffer = LineBuffer::new("ls /usr/lo");
+    assert_eq!(buffer.delete_alnum_word_before(), "lo");
+    assert_eq!(buffer.delete_before(), "/");
+    buffer.insert('日');
+    assert_eq!(buffer.text(), "ls /usr日");
+
+    buffer.move_home();
+    assert_eq!(buffer.delete_word_after(), "ls");
+    assert_eq!(buffer.delete_after(), " ");
+    buffer.move_right();
+    assert_eq!(buffer.delete_to_end(), "usr日");
+    assert_eq!(buffer.delete_to_start(), "/");
+    assert!(buffer.is_empty());
+}
+
+#[test]
+fn test_search_matching() {
+    let substring = |command, query| find_match(command, query, SearchMode::Substring);
+    assert_eq!(substring("git commit", "com"), Some(vec![4, 5, 6]));
+    // the last occurrence, ignoring case unless the query has capitals
+    assert_eq!(substring("ls LS", "ls"), Some(vec![3, 4]));
+    assert_eq!(substring("ls LS", "Ls"), None);
+    assert_eq!(substring("écho é", "é"), Some(vec![6]));
+
+    let fuzzy = |command, query| find_match(command, query, SearchMode::Fuzzy);
+    assert_eq!(fuzzy("git commit", "gcm"), Some(vec![0, 4, 6]));
+    assert_eq!(fuzzy("git commit", "mg"), None);
+    assert_eq!(fuzzy("git", ""), Some(vec![]));
+}
+
+#[test]
+fn test_search_history() {
+    let entries: Vec<Entry> = [
+        ("make test", "/src"),
+        ("cargo test", "/src"),
+        ("ls", "/tmp"),
+        ("make test", "/tmp"),
+        ("cat notes", "/tmp"),
+    ]
+    .iter()
+    .map(|(command, cwd)| Entry {
+        cwd: cwd.to_string(),
+        ..Entry::new(command)
+    })
+    .collect();
+    let found = |search: &Search| search.current().map(|(index, _)| index);
+
+    let mut search = Search::new();
+    search.query = "test".to_string();
+    search.update(&entries);
+    // newest first, and the older run of `make test` isn't found again
+    assert_eq!(search.len(), 2);
+    assert_eq!(found(&search), Some(3));
+    search.older();
+    assert_eq!(found(&search), Some(1));
+    search.older();
+    assert_eq!(found(&search), Some(1));
+    search.newer();
+    assert_eq!(found(&search), Some(3));
+
+    search.query = "ct".to_string();
+    search.update(&entries);
+    assert!(search.is_empty());
+    search.toggle_mode();
+    search.update(&entries);
+    assert_eq!(search.len(), 2);
+    assert_eq!(found(&search), Some(4));
+
+    search.mode = SearchMode::Substring;
+    search.query = "a".to_string();
+    search.directory = Some("/src".to_string());
+    search.update(&entries);
+    assert_eq!(found(&search), Some(1));
+    assert_eq!(search.len(), 2);
+}
+
+#[test]
+fn test_completion_word() {
+    assert_eq!(current_word("gi"), (0, true));
+    assert_eq!(current_word("git ch"), (4, false));
+    assert_eq!(current_word("ls | gr"), (5, true));
+    assert_eq!(current_word("a && b;c"), (7, true));
+    assert_eq!(current_word("echo 'a b"), (5, false));
+    assert_eq!(current_word("cat a\\ b"), (4, false));
+
+    assert_eq!(
+        command_words("ls; git  commit -m 'a b"),
+        [(4, "git"), (9, "commit"), (16, "-m"), (19, "'a b")]
+    );
+    assert_eq!(command_words("git add "), [(0, "git"), (4, "add"), (8, "")]);
+}
+
+#[test]
+fn test_completion_candidates() {
+    let names = |names: &[&str]| {
+        names
+            .iter()
+            .map(|name| name.to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        common_prefix(&names(&["grep", "groups", "gresource"])),
+        "gr"
+    );
+    assert_eq!(common_prefix(&names(&["héllo", "hélp"])), "hél");
+    assert_eq!(common_prefix(&names(&["ls", "lsblk"])), "ls");
+    assert_eq!(common_prefix(&[]), "");
+
+    // filled top to bottom, then left to right
+    let menu = menu_rows(&names(&["a", "bb", "ccc", "d", "e"]), 16);
+    assert_eq!(menu, ["a    ccc  e", "bb   d"]);
+    assert_eq!(menu_rows(&names(&["long-name"]), 4), ["long-name"]);
+}
+
+#[test]
+fn test_completion_quoting() {
+    let lookup = |name: &str| match name {
+        "HOME" => Some("/home/me".to_string()),
+        "DIR" => Some("/srv/a b".to_string()),
+        _ => None,
+    };
+    assert_eq!(unquote("my\\ fi", &lookup), ("my fi".to_string(), None));
+    assert_eq!(
+        unquote("'it'\\''s", &lookup),
+        ("it's".to_string(), Some('\''))
+    );
+    assert_eq!(
+        unquote("\"$DIR/x", &lookup),
+        ("/srv/a b/x".to_string(), Some('"'))
+    );
+    assert_eq!(unquote("${DIR}/", &lookup), ("/srv/a b/".to_string(), None));
+    assert_eq!(
+        unquote("~/Doc", &lookup),
+        ("/home/me/Doc".to_string(), None)
+    );
+    assert_eq!(unquote("a~", &lookup), ("a~".to_string(), None));
+
+    assert_eq!(
+        quote("it's a $5 [deal]", None),
+        "it\\'s\\ a\\ \\$5\\ \\[deal]"
+    );
+    assert_eq!(quote("it's", Some('\'')), "it'\\''s");
+    assert_eq!(quote("say \"$hi\"", Some('"')), "say \\\"\\$hi\\\"");
+    assert_eq!(quote("plain-name.rs", None), "plain-name.rs");
+}
+
+#[test]
+fn test_path_candidates() {
+    let root = std::env::temp_dir().join(format!("sosh-complete-{}", std::process::id()));
+    for directory in ["src", ".git", "my dir"] {
+        std::fs::create_dir_all(root.join(directory)).unwrap();
+    }
+    for file in ["setup.sh", ".env", "src/main.rs"] {
+        std::fs::write(root.join(file), "").unwrap();
+    }
+    let root = format!("{}/", root.display());
+
+    let names = |path: &str, programs| path_candidates(&format!("{}{}", root, path), programs);
+    assert_eq!(names("s", false), ["setup.sh", "src/"]);
+    assert_eq!(names("", false), ["my dir/", "setup.sh", "src/"]);
+    assert_eq!(names(".", false), [".env", ".git/"]);
+    assert_eq!(names("src/", false), ["main.rs"]);
+    // only directories and executables can be run
+    assert_eq!(names("s", true), ["src/"]);
+    assert!(names("missing/", false).is_empty());
+
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+fn context(words: &[&str], word: &str) -> CompletionContext {
+    let mut line = words.join(" ");
+    line.push(' ');
+    line.push_str(word);
+    CompletionContext {
+        cursor: line.len(),
+        line,
+        words: words.iter().map(|word| word.to_string()).collect(),
+        word: word.to_string(),
+    }
+}
+
+#[test]
+fn test_programmable_completion() {
+    let mut shell = Shell::new();
+    shell.set_completer(
+        "cargo",
+        |_: &mut Interpreter, context: &CompletionContext| match context.words.len() {
+            1 => vec!["build".to_string(), "test".to_string()],
+            _ => Vec::new(),
+        },
+    );
+
+    let source = r#"
+        fun _git(word, words, cursor) {
+            if (words == "git") { return "add commit " + word + str(cursor); }
+            return "";
+        }
+        complete -F _git git
+        alias g=git
+    "#;
+    let statements = Parser::new(Tokenizer::new(source).tokenize())
+        .parse()
+        .unwrap();
+    shell.interpreter.interpret(&statements).unwrap();
+
+    let mut complete = |words: &[&str], word: &str| {
+        let words: Vec<String> = words.iter().map(|word| word.to_string()).collect();
+        let (completer, words) = shell.completer(&words)?;
+        let words: Vec<&str> = words.iter().map(String::as_str).collect();
+        Some(completer.complete(&mut shell.interpreter, &context(&words, word)))
+    };
+    assert_eq!(
+        complete(&["cargo"], "b"),
+        Some(vec!["build".into(), "test".into()])
+    );
+    assert_eq!(complete(&["cargo", "build"], ""), Some(vec![]));
+    assert_eq!(
+        complete(&["git"], "co"),
+        Some(vec!["add".into(), "commit".into(), "co6".into()])
+    );
+    // an alias completes like the command it stands for
+    assert_eq!(
+        complete(&["g"], ""),
+        Some(vec!["add".into(), "commit".into(), "4".into()])
+    );
+    assert_eq!(complete(&["ls"], ""), None);
+}
+
+#[test]
+fn test_highlight() {
+    let theme = Theme::default();
+    let is_command = |name: &str| name == "ls" || name == "grep";
+    let colour = |line: &str| highlight(line, &theme, &is_command);
+    let strip = |text: &str| {
+        let mut plain = String::new();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.by_ref().find(|&c| c == 'm');
+            } else {
+                plain.push(c);
+            }
+        }
+        plain
+    };
+
+    // the text itself never changes
+    for line in [
+        "ls -la | grep x > out",
+        "let x = \"a\" + 1 // c",
+        "if (x) { ls",
+        "",
+    ] {
+        assert_eq!(strip(&colour(line)), line);
+    }
+
+    assert_eq!(
+        colour("ls -la | nope"),
+        "\x1b[32mls\x1b[0m -la \x1b[36m|\x1b[0m \x1b[31mnope\x1b[0m"
+    );
+    // assignments and redirection targets aren't the command
+    assert_eq!(
+        colour("FOO=1 ls > grep"),
+        "FOO=1 \x1b[32mls\x1b[0m \x1b[36m>\x1b[0m grep"
+    );
+    assert_eq!(
+        colour("let x = \"a"),
+        "\x1b[1;35mlet\x1b[0m x \x1b[36m=\x1b[0m \x1b[4;31m\"a\x1b[0m"
+    );
+    assert_eq!(colour("ls 'a b"), "\x1b[32mls\x1b[0m \x1b[4;31m'a b\x1b[0m");
+    assert_eq!(
+        colour("if (x) { ls"),
+        "\x1b[1;35mif\x1b[0m (x) \x1b[4;31m{\x1b[0m \x1b[32mls\x1b[0m"
+    );
+    assert_eq!(colour("f(1))"), "f(\x1b[34m1\x1b[0m)\x1b[4;31m)\x1b[0m");
+
+    let theme = Theme::parse("keyword=1:missing=7:bogus=1");
+    assert_eq!(theme.keyword, "1");
+    assert_eq!(theme.missing, "7");
+    assert_eq!(theme.string, Theme::default().string);
+}
+
+#[test]
+fn test_keymap() {
+    assert_eq!(
+        parse_keys("C-x C-e").unwrap(),
+        [Key::Ctrl('x'), Key::Ctrl('e')]
+    );
+    assert_eq!(
+        parse_keys("M-. up Space C-I C-h").unwrap(),
+        [
+            Key::Alt('.'),
+            Key::Up,
+            Key::Char(' '),
+            Key::Tab,
+            Key::Backspace
+        ]
+    );
+    assert!(parse_keys("C-1").is_err());
+    assert!(parse_keys("Hyper-x").is_err());
+    assert!(parse_keys("").is_err());
+    assert_eq!(
+        format_keys(&[Key::Ctrl('x'), Key::AltBackspace, Key::Char('q')]),
+        "C-x M-Backspace q"
+    );
+
+    let mut keymap = Keymap::emacs();
+    let accept = Binding::Action(Action::AcceptLine);
+    assert_eq!(keymap.lookup(&[Key::Ctrl('j')]), Lookup::Bound(&accept));
+    assert_eq!(keymap.lookup(&[Key::Char('x')]), Lookup::Unbound);
+
+    let command = Binding::Command("neofetch".to_string());
+    keymap.bind(parse_keys("C-x n").unwrap(), command.clone());
+    assert_eq!(keymap.lookup(&[Key::Ctrl('x')]), Lookup::Prefix);
+    assert_eq!(
+        keymap.lookup(&[Key::Ctrl('x'), Key::Char('n')]),
+        Lookup::Bound(&command)
+    );
+    assert_eq!(
+        keymap.unbind(&[Key::Ctrl('x'), Key::Char('n')]),
+        Some(command)
+    );
+    assert_eq!(keymap.lookup(&[Key::Ctrl('x')]), Lookup::Unbound);
+
+    assert_eq!(Action::from_name("kill-line"), Some(Action::KillLine));
+    assert_eq!(Action::KillLine.name(), "kill-line");
+    assert!(Action::names().all(|name| Action::from_name(name).unwrap().name() == name));
+}
+
+#[test]
+fn test_vi_parse() {
+    let parse = |keys: &str| vi::parse(&keys.chars().collect::<Vec<_>>());
+    assert_eq!(
+        parse("2d3w"),
+        Parse::Complete(
+            Some(6),
+            Command::Operate(Operator::Delete, Motion::WordForward { big: false })
+        )
+    );
+    assert_eq!(
+        parse("cc"),
+        Parse::Complete(None, Command::Operate(Operator::Change, Motion::Line))
+    );
+    assert_eq!(
+        parse("10l"),
+        Parse::Complete(Some(10), Command::Move(Motion::Right))
+    );
+    assert_eq!(
+        parse("99999999999999999999999d99999999999w"),
+        Parse::Complete(
+            Some(vi::MAX_COUNT),
+            Command::Operate(Operator::Delete, Motion::WordForward { big: false })
+        )
+    );
+    assert_eq!(
+        parse("0"),
+        Parse::Complete(None, Command::Move(Motion::Start))
+    );
+    assert_eq!(
+        parse("tx"),
+        Parse::Complete(
+            None,
+            Command::Move(Motion::Find {
+                target: 'x',
+                forward: true,
+                till: true
+            })
+        )
+    );
+    for incomplete in ["3", "d", "d2", "f", "r"] {
+        assert_eq!(parse(incomplete), Parse::Incomplete, "{}", incomplete);
+    }
+    assert_eq!(parse("dq"), Parse::Invalid);
+    assert_eq!(parse("Q"), Parse::Invalid);
+}
+
+#[test]
+fn test_vi_editing() {
+    let mut vi = Vi::new();
+    let mut buffer = LineBuffer::new("echo one two three");
+    let mut register = String::new();
+    vi.start_line(&buffer);
+    vi.enter_normal(&mut buffer);
+    assert_eq!(vi.mode, Mode::Normal);
+
+    let mut keys = |vi: &mut Vi, buffer: &mut LineBuffer, keys: &str| {
+        for c in keys.chars() {
+            if vi.mode == Mode::Insert {
+                if c == '\x1b' {
+                    vi.enter_normal(buffer);
+                } else {
+                    buffer.insert(c);
+                    vi.record(c);
+                }
+            } else {
+                vi.feed(c, buffer, &mut register);
+            }
+        }
+    };
+
+    keys(&mut vi, &mut buffer, "0wdw");
+    assert_eq!(buffer.text(), "echo two three");
+    assert_eq!(buffer.cursor(), 5);
+
+    // `cw` keeps the space after the word, and `.` does it again with the same text
+    keys(&mut vi, &mut buffer, "cwTWO\x1bw.");
+    assert_eq!(buffer.text(), "echo TWO TWO");
+    keys(&mut vi, &mut buffer, "u");
+    assert_eq!(buffer.text(), "echo TWO three");
+    keys(&mut vi, &mut buffer, "uu");
+    assert_eq!(buffer.text(), "echo one two three");
+
+    keys(&mut vi, &mut buffer, "$Fod$");
+    assert_eq!(buffer.text(), "echo one tw");
+    keys(&mut vi, &mut buffer, "0fe;D");
+    assert_eq!(buffer.text(), "echo on");
+    assert_eq!(buffer.cursor(), "echo o".len());
+
+    keys(&mut vi, &mut buffer, "0yeA \x1bp");
+    assert_eq!(buffer.text(), "echo on echo");
+    keys(&mut vi, &mut buffer, "02x");
+    assert_eq!(buffer.text(), "ho on echo");
+    keys(&mut vi, &mut buffer, "3rx");
+    assert_eq!(buffer.text(), "xxxon echo");
+    keys(&mut vi, &mut buffer, "dd");
+    assert_eq!(buffer.text(), "");
+
+    // counts too big to be meant stop at the limit instead of overflowing
+    keys(&mut vi, &mut buffer, "iab\x1b0yl99999999999999999999999p");
+    assert_eq!(buffer.text().len(), 2 + vi::MAX_COUNT);
+
+    // a line break in a multi-line entry is a cell of its own, not part of the one before it
+    keys(&mut vi, &mut buffer, "ddiab\ncd\x1b$hhx");
+    assert_eq!(buffer.text(), "abcd");
+}
+
+#[test]
+fn test_truncate_styled() {
+    assert_eq!(truncate("\x1b[1mabc\x1b[0m", 2), ("\x1b[1mab", 2));
+    assert_eq!(truncate("日本語", 5), ("日本", 4));
+}
@@ -0,0 +1,545 @@
+use sheesh::editor::keymap::Lookup;
+use sheesh::editor::{Action, Binding, Key};
+use sheesh::interpreter::*;
+use sheesh::shell::Shell;
+
+fn run(source: &str) -> Result<Value, RuntimeError> {
+    let tokens = Tokenizer::new(source).tokenize();
+    let statements = Parser::new(tokens).parse().expect("source should parse");
+    Interpreter::new().interpret(&statements)
+}
+
+fn run_and_get(source: &str, name: &str) -> Option<Value> {
+    let tokens = Tokenizer::new(source).tokenize();
+    let statements = Parser::new(tokens).parse().expect("source should parse");
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .interpret(&statements)
+        .expect("source should run");
+    interpreter.get(name)
+}
+
+#[test]
+fn test_arithmetic_precedence() {
+    assert_eq!(run("2 + 3 * 4 - 5 / 2;"), Ok(Value::Number(11.5)));
+    assert_eq!(run("-(1 + 2) % 2;"), Ok(Value::Number(-1.0)));
+}
+
+#[test]
+fn test_let_and_compound_assignment() {
+    let result = run_and_get(
+        r#"
+        let x = 1;
+        x += 4;
+        x *= 2;
+        "#,
+        "x",
+    );
+    assert_eq!(result, Some(Value::Number(10.0)));
+}
+
+#[test]
+fn test_const_cannot_be_reassigned() {
+    let result = run("const y = 1; y = 2;");
+    assert_eq!(result, Err(RuntimeError::ConstAssignment("y".to_string())));
+
+    // nor defined again in the same scope, though an inner one may hide it
+    let result = run("const y = 1; let y = 2;");
+    assert_eq!(result, Err(RuntimeError::ConstAssignment("y".to_string())));
+    assert_eq!(
+        run("const y = 1; fun f() { let y = 2; return y; } f();"),
+        Ok(Value::Number(2.0))
+    );
+
+    // `nil`, `true` and `false` can't be replaced anywhere
+    for (source, name) in [
+        ("let nil = 1;", "nil"),
+        ("let true = 1;", "true"),
+        ("{ const false = true; }", "false"),
+        ("fun f(nil) {} f(1);", "nil"),
+    ] {
+        assert_eq!(
+            run(source),
+            Err(RuntimeError::ConstAssignment(name.to_string())),
+            "{}",
+            source
+        );
+    }
+}
+
+#[test]
+fn test_undefined_variable() {
+    assert_eq!(
+        run("z + 1;"),
+        Err(RuntimeError::UndefinedVariable("z".to_string()))
+    );
+}
+
+#[test]
+fn test_block_scoping() {
+    let result = run_and_get(
+        r#"
+        let x = 1;
+        {
+            let x = 2;
+            x = 3;
+        }
+        "#,
+        "x",
+    );
+    assert_eq!(result, Some(Value::Number(1.0)));
+}
+
+#[test]
+fn test_if_else_and_while() {
+    let result = run_and_get(
+        r#"
+        let i = 0;
+        let evens = 0;
+        while (i < 10) {
+            if (i % 2 == 0) {
+                evens += 1;
+            } else {
+                evens += 0;
+            }
+            i += 1;
+        }
+        "#,
+        "evens",
+    );
+    assert_eq!(result, Some(Value::Number(5.0)));
+}
+
+#[test]
+fn test_function_call_and_return_unwinding() {
+    let result = run(r#"
+        fun first_over(limit) {
+            let i = 0;
+            while (true) {
+                if (i * i > limit) {
+                    return i;
+                }
+                i += 1;
+            }
+        }
+        first_over(50);
+        "#);
+    assert_eq!(result, Ok(Value::Number(8.0)));
+}
+
+#[test]
+fn test_recursion_and_closures() {
+    let result = run(r#"
+        fun fib(n) {
+            if (n < 2) {
+                return n;
+            }
+            return fib(n - 1) + fib(n - 2);
+        }
+
+        let offset = 100;
+        fun shifted(n) {
+            return fib(n) + offset;
+        }
+        offset = 1000;
+        shifted(10);
+        "#);
+    assert_eq!(result, Ok(Value::Number(1055.0)));
+}
+
+#[test]
+fn test_call_errors() {
+    assert_eq!(
+        run("fun f(a) { return a; } f(1, 2);"),
+        Err(RuntimeError::ArityMismatch {
+            name: "f".to_string(),
+            expected: 1,
+            got: 2
+        })
+    );
+    assert!(matches!(
+        run("let x = 1; x();"),
+        Err(RuntimeError::NotCallable(_))
+    ));
+    assert_eq!(run("1 / 0;"), Err(RuntimeError::DivisionByZero));
+    assert_eq!(run("return 1;"), Err(RuntimeError::ReturnOutsideFunction));
+}
+
+#[test]
+fn test_string_values() {
+    let statements = vec![Stmt::Expr(Expr::Binary(
+        Box::new(Expr::String("answer: ".to_string())),
+        BinOp::Add,
+        Box::new(Expr::Number(42.0)),
+    ))];
+
+    let result = Interpreter::new().interpret(&statements);
+    assert_eq!(result, Ok(Value::String("answer: 42".to_string())));
+}
+
+#[test]
+fn test_native_functions() {
+    let statements = vec![Stmt::Expr(Expr::Call(
+        Box::new(Expr::Identifier("len".to_string())),
+        vec![Expr::String("sheesh".to_string())],
+    ))];
+
+    let result = Interpreter::new().interpret(&statements);
+    assert_eq!(result, Ok(Value::Number(6.0)));
+}
+
+#[test]
+fn test_tokenized_source_end_to_end() {
+    let mut interpreter = Interpreter::new();
+    for line in [
+        "let name = \"sosh\"",
+        "fun greet(who) { return \"hi \" + who; }",
+    ] {
+        let statements = Parser::new(Tokenizer::new(line).tokenize())
+            .parse()
+            .unwrap();
+        interpreter.interpret(&statements).unwrap();
+    }
+
+    // state is kept between separate inputs, like prompts in the REPL
+    let statements = Parser::new(Tokenizer::new("greet(name)").tokenize())
+        .parse()
+        .unwrap();
+    assert_eq!(
+        interpreter.interpret(&statements),
+        Ok(Value::String("hi sosh".to_string()))
+    );
+}
+
+fn run_in(interpreter: &mut Interpreter, source: &str) -> Result<Value, RuntimeError> {
+    let statements = Parser::new(Tokenizer::new(source).tokenize())
+        .parse()
+        .expect("source should parse");
+    interpreter.interpret(&statements)
+}
+
+#[test]
+fn test_external_command_status() {
+    let mut interpreter = Interpreter::new();
+
+    run_in(&mut interpreter, "true").unwrap();
+    assert_eq!(interpreter.last_status(), 0);
+    run_in(&mut interpreter, "false").unwrap();
+    assert_eq!(interpreter.last_status(), 1);
+    run_in(&mut interpreter, "sh -c 'exit 7'").unwrap();
+    assert_eq!(interpreter.last_status(), 7);
+    run_in(&mut interpreter, "definitely-not-a-sosh-command").unwrap();
+    assert_eq!(interpreter.last_status(), 127);
+}
+
+#[test]
+fn test_command_environment_and_redirects() {
+    let output = std::env::temp_dir().join(format!("sosh-redirect-{}", std::process::id()));
+    let mut interpreter = Interpreter::new();
+
+    let source = format!(
+        "let word = \"shell\"\nGREETING=sheesh sh -c 'echo $GREETING' > {path}\necho \"$word\" >> {path}",
+        path = output.display()
+    );
+    run_in(&mut interpreter, &source).unwrap();
+
+    assert_eq!(std::fs::read_to_string(&output).unwrap(), "sheesh\nshell\n");
+    assert!(std::env::var_os("GREETING").is_none());
+    std::fs::remove_file(output).unwrap();
+}
+
+#[test]
+fn test_positional_parameters() {
+    let mut interpreter = Interpreter::new();
+    assert_eq!(interpreter.lookup_variable("0").as_deref(), Some("sosh"));
+    assert_eq!(interpreter.lookup_variable("#").as_deref(), Some("0"));
+    assert_eq!(interpreter.lookup_variable("@").as_deref(), Some(""));
+    assert_eq!(interpreter.lookup_variable("1"), None);
+
+    interpreter.arguments = ["run.sosh", "one", "two words"].map(String::from).to_vec();
+    assert_eq!(
+        interpreter.lookup_variable("0").as_deref(),
+        Some("run.sosh")
+    );
+    assert_eq!(
+        interpreter.lookup_variable("2").as_deref(),
+        Some("two words")
+    );
+    assert_eq!(interpreter.lookup_variable("3"), None);
+    assert_eq!(interpreter.lookup_variable("#").as_deref(), Some("2"));
+    assert_eq!(
+        interpreter.lookup_variable("*").as_deref(),
+        Some("one two words")
+    );
+}
+
+#[test]
+fn test_descriptor_redirects() {
+    let output = std::env::temp_dir().join(format!("sosh-descriptors-{}", std::process::id()));
+    let mut interpreter = Interpreter::new();
+
+    let source = format!(
+        "sh -c 'echo out; echo err >&2' > {path} 2>&1\nsh -c 'echo both >&2' &>> {path}",
+        path = output.display()
+    );
+    run_in(&mut interpreter, &source).unwrap();
+
+    assert_eq!(
+        std::fs::read_to_string(&output).unwrap(),
+        "out\nerr\nboth\n"
+    );
+    std::fs::remove_file(output).unwrap();
+}
+
+#[test]
+fn test_redirect_errors_fail_the_command() {
+    let mut interpreter = Interpreter::new();
+
+    run_in(&mut interpreter, "true > /nonexistent-sosh-dir/out").unwrap();
+    assert_eq!(interpreter.last_status(), 1);
+    run_in(&mut interpreter, "true < /nonexistent-sosh-dir/in").unwrap();
+    assert_eq!(interpreter.last_status(), 1);
+    run_in(&mut interpreter, "true 2>&9").unwrap();
+    assert_eq!(interpreter.last_status(), 1);
+}
+
+#[test]
+fn test_here_documents_and_strings() {
+    let output = std::env::temp_dir().join(format!("sosh-here-doc-{}", std::process::id()));
+    let mut interpreter = Interpreter::new();
+
+    let source = format!(
+        "let name = \"sosh\"\ncat > {path} <<EOF\nhi $name\nEOF\ncat >> {path} <<-'EOF'\n\t$name\n\tEOF\ntr a-z A-Z >> {path} <<< \"$name rocks\"",
+        path = output.display()
+    );
+    run_in(&mut interpreter, &source).unwrap();
+
+    assert_eq!(
+        std::fs::read_to_string(&output).unwrap(),
+        "hi sosh\n$name\nSOSH ROCKS\n"
+    );
+    std::fs::remove_file(output).unwrap();
+}
+
+#[test]
+fn test_assignment_only_command_sets_variable() {
+    let mut interpreter = Interpreter::new();
+    run_in(&mut interpreter, "name=sosh").unwrap();
+    assert_eq!(
+        interpreter.get("name"),
+        Some(Value::String("sosh".to_string()))
+    );
+}
+
+#[test]
+fn test_assignments_before_function_last_for_the_call() {
+    let mut interpreter = Interpreter::new();
+    run_in(
+        &mut interpreter,
+        "let code = \"1\"\nfun exit_code() { return num(code); }\ncode=42 SOSH_PREFIX=set exit_code",
+    )
+    .unwrap();
+    assert_eq!(interpreter.last_status(), 42);
+    assert_eq!(
+        interpreter.get("code"),
+        Some(Value::String("1".to_string()))
+    );
+    assert_eq!(std::env::var_os("SOSH_PREFIX"), None);
+
+    // a constant can't be changed even for one command
+    assert_eq!(
+        run_in(&mut interpreter, "const fixed = 1\nfixed=2 exit_code"),
+        Err(RuntimeError::ConstAssignment("fixed".to_string()))
+    );
+}
+
+#[test]
+fn test_function_called_as_command() {
+    let mut interpreter = Interpreter::new();
+    run_in(
+        &mut interpreter,
+        "fun fail_with(code) { return num(code); }\nfail_with 42",
+    )
+    .unwrap();
+    assert_eq!(interpreter.last_status(), 42);
+}
+
+#[test]
+fn test_exit_builtin_unwinds() {
+    let mut interpreter = Interpreter::new();
+    assert_eq!(
+        run_in(&mut interpreter, "exit 3"),
+        Err(RuntimeError::Exit(3))
+    );
+}
+
+#[test]
+fn test_pipeline_streams_between_processes() {
+    let output = std::env::temp_dir().join(format!("sosh-pipeline-{}", std::process::id()));
+    let mut interpreter = Interpreter::new();
+
+    let source = format!(
+        "printf 'b\\na\\nc\\n' | sort | head -n 2 > {}",
+        output.display()
+    );
+    run_in(&mut interpreter, &source).unwrap();
+
+    assert_eq!(std::fs::read_to_string(&output).unwrap(), "a\nb\n");
+    assert_eq!(interpreter.last_status(), 0);
+    std::fs::remove_file(output).unwrap();
+}
+
+#[test]
+fn test_pipeline_status_and_pipefail() {
+    let mut interpreter = Interpreter::new();
+
+    run_in(&mut interpreter, "true | false").unwrap();
+    assert_eq!(interpreter.last_status(), 1);
+    run_in(&mut interpreter, "sh -c 'exit 3' | true").unwrap();
+    assert_eq!(interpreter.last_status(), 0);
+
+    interpreter.options.pipefail = true;
+    run_in(&mut interpreter, "sh -c 'exit 3' | true").unwrap();
+    assert_eq!(interpreter.last_status(), 3);
+}
+
+#[test]
+fn test_background_jobs_and_wait() {
+    let mut interpreter = Interpreter::new();
+
+    run_in(&mut interpreter, "sh -c 'exit 3' &\nsleep 0.2 | true &").unwrap();
+    assert_eq!(interpreter.last_status(), 0);
+    assert_eq!(interpreter.jobs.len(), 2);
+
+    run_in(&mut interpreter, "wait %1").unwrap();
+    assert_eq!(interpreter.last_status(), 3);
+    run_in(&mut interpreter, "wait $!").unwrap();
+    assert_eq!(interpreter.last_status(), 0);
+    assert!(interpreter.jobs.is_empty());
+
+    run_in(&mut interpreter, "wait %1").unwrap();
+    assert_eq!(interpreter.last_status(), 127);
+}
+
+#[test]
+fn test_kill_and_disown_jobs() {
+    let mut interpreter = Interpreter::new();
+
+    run_in(&mut interpreter, "sleep 5 &\nsleep 5 &\nkill %1\nwait %1").unwrap();
+    assert_eq!(interpreter.last_status(), 128 + 15);
+
+    run_in(&mut interpreter, "kill -KILL %%\ndisown").unwrap();
+    assert!(interpreter.jobs.is_empty());
+    run_in(&mut interpreter, "fg").unwrap();
+    assert_eq!(interpreter.last_status(), 1);
+}
+
+#[test]
+fn test_children_get_default_signal_handling() {
+    let mut interpreter = Interpreter::new();
+    sheesh::interpreter::signals::install();
+
+    // the shell ignores SIGQUIT, the child must die from it
+    run_in(&mut interpreter, "sh -c 'kill -QUIT $$'").unwrap();
+    assert_eq!(interpreter.last_status(), 128 + 3);
+}
+
+#[test]
+fn test_aliases() {
+    let output = std::env::temp_dir().join(format!("sosh-alias-{}", std::process::id()));
+    let mut interpreter = Interpreter::new();
+
+    let source = format!(
+        "alias say='printf %s-'\nalias printf='printf [%s]'\nsay a > {}\nprintf b >> {}",
+        output.display(),
+        output.display()
+    );
+    run_in(&mut interpreter, &source).unwrap();
+    // an alias isn't expanded again, neither in its own value nor inside another alias
+    assert_eq!(std::fs::read_to_string(&output).unwrap(), "a-[b]");
+    std::fs::remove_file(output).unwrap();
+
+    run_in(&mut interpreter, "alias bad='a | b'").unwrap();
+    assert_eq!(interpreter.last_status(), 1);
+    run_in(&mut interpreter, "unalias say\nsay").unwrap();
+    assert_eq!(interpreter.last_status(), 127);
+}
+
+#[test]
+fn test_command_names() {
+    let mut interpreter = Interpreter::new();
+    run_in(
+        &mut interpreter,
+        "fun unpack_all() { return 0; }\nalias unz=unzip",
+    )
+    .unwrap();
+
+    let names = interpreter.command_names("un");
+    for name in ["unalias", "unpack_all", "unz"] {
+        assert!(names.contains(&name.to_string()), "{:?}", names);
+    }
+    assert!(names.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(interpreter.command_names("sh").contains(&"sh".to_string()));
+    assert!(interpreter.command_names("no-such-command").is_empty());
+}
+
+#[test]
+fn test_bind() {
+    let mut shell = Shell::new();
+    run_in(
+        &mut shell.interpreter,
+        "bind -x C-j 'neofetch'\nbind 'C-x C-e' end-of-line\nbind -m vi-insert C-a beginning-of-line",
+    )
+    .unwrap();
+    assert_eq!(shell.interpreter.last_status(), 0);
+
+    let keymaps = shell.keymaps.borrow();
+    let emacs = &keymaps["emacs"];
+    assert_eq!(
+        emacs.lookup(&[Key::Ctrl('j')]),
+        Lookup::Bound(&Binding::Command("neofetch".to_string()))
+    );
+    assert_eq!(
+        emacs.lookup(&[Key::Ctrl('x'), Key::Ctrl('e')]),
+        Lookup::Bound(&Binding::Action(Action::EndOfLine))
+    );
+    assert_eq!(
+        keymaps["vi-insert"].lookup(&[Key::Ctrl('a')]),
+        Lookup::Bound(&Binding::Action(Action::BeginningOfLine))
+    );
+    drop(keymaps);
+
+    for (line, status) in [
+        ("bind C-a no-such-action", 1),
+        ("bind Hyper-a end-of-line", 1),
+        ("bind -m nope -l", 1),
+        ("bind -r C-q", 1),
+        ("bind -r C-j", 0),
+        ("bind -x C-j", 2),
+    ] {
+        run_in(&mut shell.interpreter, line).unwrap();
+        assert_eq!(shell.interpreter.last_status(), status, "{}", line);
+    }
+    assert_eq!(
+        shell.keymaps.borrow()["emacs"].lookup(&[Key::Ctrl('j')]),
+        Lookup::Unbound
+    );
+}
+
+#[test]
+fn test_editing_mode() {
+    let mut shell = Shell::new();
+    run_in(
+        &mut shell.interpreter,
+        "set -o vi\nbind C-a beginning-of-line",
+    )
+    .unwrap();
+    assert!(shell.interpreter.options.vi);
+    // `bind` goes to the keymap typing uses
+    assert_eq!(
+        shell.keymaps.borrow()["vi-insert"].lookup(&[Key::Ctrl('a')]),
+        Lookup::Bound(&Binding::Action(Action::BeginningOfLine))
+    );
+    run_in(&mut shell.interpreter, "set -o emacs").unwrap();
+    assert!(!shell.interpreter.options.vi);
+}
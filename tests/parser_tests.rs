@@ -376,4 +376,191 @@ fn test_multi_operation_box() {
     } else {
         panic!("Expected function declaration");
     }
-}
\ No newline at end of file
+}
+#[test]
+fn test_last_statement_semicolon_is_optional() {
+    let tokens = vec![
+        Token::new(TokenKind::Literal(LiteralTokenKind::Identifier), "x"),
+        Token::new(TokenKind::Operator(OperatorTokenKind::Plus), "+"),
+        Token::new(TokenKind::Literal(LiteralTokenKind::Number), "1"),
+        Token::new(TokenKind::EOF, ""),
+    ];
+
+    let mut parser = Parser::new(tokens);
+    let result = parser.parse().unwrap();
+
+    assert_eq!(result.len(), 1);
+    assert!(matches!(
+        result[0],
+        Stmt::Expr(Expr::Binary(_, BinOp::Add, _))
+    ));
+}
+
+#[test]
+fn test_newlines_terminate_statements() {
+    let tokens =
+        sheesh::Tokenizer::new("let a = 1\n\nlet b = a +\n  2 // trailing\n{ b = 3 }").tokenize();
+
+    let mut parser = Parser::new(tokens);
+    let result = parser.parse().unwrap();
+
+    assert_eq!(result.len(), 3);
+    assert!(matches!(
+        result[1],
+        Stmt::Let(ref name, Expr::Binary(_, BinOp::Add, _)) if name == "b"
+    ));
+    assert!(matches!(result[2], Stmt::Block(ref statements) if statements.len() == 1));
+}
+
+#[test]
+fn test_parse_command() {
+    let tokens = vec![
+        Token::new(TokenKind::Literal(LiteralTokenKind::Word), "LANG=C"),
+        Token::new(TokenKind::Literal(LiteralTokenKind::Word), "sort"),
+        Token::new(TokenKind::Literal(LiteralTokenKind::Word), "-r"),
+        Token::new(TokenKind::Operator(OperatorTokenKind::Less), "<"),
+        Token::new(TokenKind::Literal(LiteralTokenKind::Word), "in.txt"),
+        Token::new(TokenKind::Operator(OperatorTokenKind::DoubleGreater), ">>"),
+        Token::new(TokenKind::Literal(LiteralTokenKind::Word), "'out file'"),
+        Token::new(TokenKind::Punctuation(PunctuationTokenKind::Newline), "\n"),
+        Token::new(TokenKind::Literal(LiteralTokenKind::Word), "ls"),
+        Token::new(TokenKind::EOF, ""),
+    ];
+
+    let mut parser = Parser::new(tokens);
+    let result = parser.parse().unwrap();
+
+    assert_eq!(result.len(), 2);
+    assert_eq!(
+        result[0],
+        Stmt::Command(Command {
+            assignments: vec![("LANG".to_string(), "C".to_string())],
+            words: vec!["sort".to_string(), "-r".to_string()],
+            redirects: vec![
+                Redirect {
+                    fd: 0,
+                    kind: RedirectKind::Input,
+                    target: "in.txt".to_string(),
+                },
+                Redirect {
+                    fd: 1,
+                    kind: RedirectKind::Append,
+                    target: "'out file'".to_string(),
+                },
+            ],
+        })
+    );
+    assert!(matches!(result[1], Stmt::Command(ref command) if command.words == vec!["ls"]));
+}
+
+#[test]
+fn test_parse_redirect_without_target() {
+    let tokens = vec![
+        Token::new(TokenKind::Literal(LiteralTokenKind::Word), "ls"),
+        Token::new(TokenKind::Operator(OperatorTokenKind::Greater), ">"),
+        Token::new(TokenKind::EOF, ""),
+    ];
+
+    let mut parser = Parser::new(tokens);
+    assert!(parser.parse().is_err());
+}
+
+#[test]
+fn test_parse_descriptor_redirects() {
+    let tokens = sheesh::Tokenizer::new("make 2>>log 3<&0 >&- &>all").tokenize();
+
+    let mut parser = Parser::new(tokens);
+    let result = parser.parse().unwrap();
+
+    let redirect = |fd, kind, target: &str| Redirect {
+        fd,
+        kind,
+        target: target.to_string(),
+    };
+    assert_eq!(
+        result[0],
+        Stmt::Command(Command {
+            assignments: vec![],
+            words: vec!["make".to_string()],
+            redirects: vec![
+                redirect(2, RedirectKind::Append, "log"),
+                redirect(3, RedirectKind::Duplicate, "0"),
+                redirect(1, RedirectKind::Duplicate, "-"),
+                redirect(1, RedirectKind::Output, "all"),
+                redirect(2, RedirectKind::Duplicate, "1"),
+            ],
+        })
+    );
+}
+
+#[test]
+fn test_parse_here_documents() {
+    let tokens =
+        sheesh::Tokenizer::new("cat <<EOF 3<<\"RAW\" <<< $name\n$a\nEOF\n$b\nRAW").tokenize();
+
+    let mut parser = Parser::new(tokens);
+    let result = parser.parse().unwrap();
+
+    assert_eq!(
+        result,
+        vec![Stmt::Command(Command {
+            assignments: vec![],
+            words: vec!["cat".to_string()],
+            redirects: vec![
+                Redirect {
+                    fd: 0,
+                    kind: RedirectKind::HereDoc { expand: true },
+                    target: "$a\n".to_string(),
+                },
+                Redirect {
+                    fd: 3,
+                    kind: RedirectKind::HereDoc { expand: false },
+                    target: "$b\n".to_string(),
+                },
+                Redirect {
+                    fd: 0,
+                    kind: RedirectKind::HereString,
+                    target: "$name".to_string(),
+                },
+            ],
+        })]
+    );
+}
+
+#[test]
+fn test_parse_background_jobs() {
+    let tokens = sheesh::Tokenizer::new("sleep 1 & make | tee log &\nwait").tokenize();
+
+    let mut parser = Parser::new(tokens);
+    let result = parser.parse().unwrap();
+
+    assert_eq!(result.len(), 3);
+    assert!(matches!(&result[0], Stmt::Background(commands) if commands.len() == 1));
+    assert!(matches!(&result[1], Stmt::Background(commands) if commands.len() == 2));
+    assert!(matches!(&result[2], Stmt::Command(command) if command.words == vec!["wait"]));
+}
+
+#[test]
+fn test_parse_pipeline() {
+    let tokens =
+        sheesh::Tokenizer::new("cat notes.txt | grep -v todo |\n  wc -l > count").tokenize();
+
+    let mut parser = Parser::new(tokens);
+    let result = parser.parse().unwrap();
+
+    assert_eq!(result.len(), 1);
+    if let Stmt::Pipeline(commands) = &result[0] {
+        let words: Vec<Vec<String>> = commands.iter().map(|c| c.words.clone()).collect();
+        assert_eq!(
+            words,
+            vec![
+                vec!["cat", "notes.txt"],
+                vec!["grep", "-v", "todo"],
+                vec!["wc", "-l"]
+            ]
+        );
+        assert_eq!(commands[2].redirects.len(), 1);
+    } else {
+        panic!("Expected pipeline");
+    }
+}
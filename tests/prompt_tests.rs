@@ -0,0 +1,81 @@
+use sheesh::interpreter::*;
+use sheesh::prompt::{expand, render, short_directory, PromptInfo};
+use std::time::Duration;
+
+fn run_in(interpreter: &mut Interpreter, source: &str) {
+    let statements = Parser::new(Tokenizer::new(source).tokenize())
+        .parse()
+        .expect("source should parse");
+    interpreter.interpret(&statements).unwrap();
+}
+
+#[test]
+fn test_prompt_escapes() {
+    let info = PromptInfo {
+        user: "sopy".to_string(),
+        host: "box.example.org".to_string(),
+        cwd: "/home/sopy/src/sheesh".to_string(),
+        home: Some("/home/sopy".to_string()),
+        status: 130,
+        duration: Some(Duration::from_millis(1500)),
+        jobs: 2,
+        git_branch: Some("main".to_string()),
+        ..PromptInfo::default()
+    };
+
+    assert_eq!(expand(r"\u@\h:\w\$ ", &info), "sopy@box:~/src/sheesh$ ");
+    assert_eq!(
+        expand(r"\H \W [\?] \D \j", &info),
+        "box.example.org sheesh [130] 1.5s 2"
+    );
+    assert_eq!(
+        expand(r"\c{bold}\c{bright-red}!\c{reset}\c{1;32}>\c{nope}", &info),
+        "\x1b[1m\x1b[91m!\x1b[0m\x1b[1;32m>\\c{nope}"
+    );
+    // unknown escapes and a trailing backslash stay as they are
+    assert_eq!(expand(r"\q\\\", &info), r"\q\\");
+    assert_eq!(expand(r"a\nb", &info), "a\nb");
+    assert_eq!(expand(r"(\g)", &info), "(main)");
+    assert_eq!(expand(r"(\g)", &PromptInfo::default()), "()");
+
+    let root = PromptInfo { root: true, ..info };
+    assert_eq!(expand(r"\$", &root), "#");
+}
+
+#[test]
+fn test_short_directory() {
+    let home = Some("/home/sopy");
+    assert_eq!(short_directory("/home/sopy", home, 0), "~");
+    assert_eq!(short_directory("/home/sopy/a/b", home, 0), "~/a/b");
+    assert_eq!(short_directory("/home/sopyx", home, 0), "/home/sopyx");
+    assert_eq!(short_directory("/", home, 0), "/");
+    assert_eq!(
+        short_directory("/usr/share/doc/rust", None, 2),
+        "/…/doc/rust"
+    );
+    assert_eq!(short_directory("/home/sopy/a/b/c", home, 2), "~/…/b/c");
+    assert_eq!(short_directory("/home/sopy/a/b", home, 2), "~/a/b");
+}
+
+#[test]
+fn test_prompt_sources() {
+    let mut interpreter = Interpreter::new();
+    assert_eq!(render(&mut interpreter, false), "sheesh $ ");
+    assert_eq!(render(&mut interpreter, true), "> ");
+
+    run_in(
+        &mut interpreter,
+        "let PROMPT = \"[\\?] \"\nlet PROMPT2 = \"..\"",
+    );
+    assert_eq!(render(&mut interpreter, false), "[0] ");
+    assert_eq!(render(&mut interpreter, true), "..");
+
+    // a prompt function wins over $PROMPT, and what it runs doesn't change the status
+    run_in(
+        &mut interpreter,
+        "fun prompt() { false; return \"fn \\?> \"; }",
+    );
+    assert_eq!(render(&mut interpreter, false), "fn 0> ");
+    assert_eq!(interpreter.last_status(), 0);
+    assert_eq!(render(&mut interpreter, true), "..");
+}
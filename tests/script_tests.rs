@@ -0,0 +1,44 @@
+use sheesh::interpreter::*;
+use sheesh::script::{run_file, run_source, ScriptError};
+use std::path::Path;
+
+#[test]
+fn test_run_source() {
+    let mut interpreter = Interpreter::new();
+    let source =
+        "// setup\nalias ll='ls -la'\nfun twice(x) {\n    return x * 2;\n}\nlet y = twice(21)\n";
+    run_source(&mut interpreter, source).unwrap();
+    assert_eq!(interpreter.get("y"), Some(Value::Number(42.0)));
+    assert_eq!(interpreter.aliases["ll"], "ls -la");
+}
+
+#[test]
+fn test_script_errors() {
+    let mut interpreter = Interpreter::new();
+
+    // nothing runs when the file doesn't parse
+    let error = run_source(&mut interpreter, "let a = 1\n\n// note\nlet = 2\n").unwrap_err();
+    assert!(matches!(error, ScriptError::Syntax { line: 4, .. }));
+    assert_eq!(interpreter.get("a"), None);
+
+    // statements before a runtime error have run
+    let source = "let b = 1\nif (true) {\n    let c = nope\n}\nlet d = 2";
+    let error = run_source(&mut interpreter, source).unwrap_err();
+    assert!(matches!(
+        error,
+        ScriptError::Runtime {
+            line: 2,
+            error: RuntimeError::UndefinedVariable(_)
+        }
+    ));
+    assert_eq!(
+        error.located(Path::new("config.sosh")),
+        "config.sosh:2: undefined variable 'nope'"
+    );
+    assert_eq!(interpreter.get("b"), Some(Value::Number(1.0)));
+    assert_eq!(interpreter.get("d"), None);
+
+    let error = run_file(&mut interpreter, Path::new("/nonexistent/config.sosh")).unwrap_err();
+    assert!(matches!(error, ScriptError::Unreadable(_)));
+    assert_eq!(error.line(), None);
+}
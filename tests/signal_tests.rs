@@ -0,0 +1,35 @@
+use sheesh::interpreter::signals;
+use sheesh::interpreter::*;
+
+// The interrupt flag is process wide, so this lives in its own test binary where no other
+// test can consume it.
+#[test]
+fn test_interrupt_breaks_out_of_loops() {
+    let mut interpreter = Interpreter::new();
+    let run = |interpreter: &mut Interpreter, source: &str| {
+        let statements = Parser::new(Tokenizer::new(source).tokenize())
+            .parse()
+            .expect("source should parse");
+        interpreter.interpret(&statements)
+    };
+
+    run(&mut interpreter, "let i = 0").unwrap();
+    signals::interrupt();
+    assert_eq!(
+        run(&mut interpreter, "while (true) { i = i + 1; }\ni = 10"),
+        Err(RuntimeError::Interrupted)
+    );
+    assert_eq!(interpreter.last_status(), 130);
+    assert_eq!(interpreter.get("i"), Some(Value::Number(0.0)));
+    assert!(!signals::take_interrupt());
+}
+
+#[test]
+fn test_resize_is_noticed() {
+    signals::install();
+    assert!(!signals::take_resize());
+    // SAFETY: the handler installed above only sets a flag
+    unsafe { libc::raise(libc::SIGWINCH) };
+    assert!(signals::take_resize());
+    assert!(!signals::take_resize());
+}
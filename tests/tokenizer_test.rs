@@ -140,4 +140,347 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_operators_take_the_longest_match() {
+        let tokens = Tokenizer::new("1 <<< 2 =< 3").tokenize();
+        let expected_tokens = vec![
+            Token::new(TokenKind::Literal(LiteralTokenKind::Number), "1"),
+            Token::new(TokenKind::Operator(OperatorTokenKind::TripleLess), "<<<"),
+            Token::new(TokenKind::Literal(LiteralTokenKind::Number), "2"),
+            // characters that don't make an operator together are two of them, not a panic
+            Token::new(TokenKind::Operator(OperatorTokenKind::Assign), "="),
+            Token::new(TokenKind::Operator(OperatorTokenKind::Less), "<"),
+            Token::new(TokenKind::Literal(LiteralTokenKind::Number), "3"),
+            Token::new(TokenKind::EOF, ""),
+        ];
+        assert_eq!(tokens, expected_tokens);
+    }
+
+    fn kinds_and_values(input: &str) -> Vec<(TokenKind, String)> {
+        Tokenizer::new(input)
+            .tokenize()
+            .into_iter()
+            .map(|token| (token.kind, token.value))
+            .collect()
+    }
+
+    fn word(value: &str) -> (TokenKind, String) {
+        (
+            TokenKind::Literal(LiteralTokenKind::Word),
+            value.to_string(),
+        )
+    }
+
+    fn eof() -> (TokenKind, String) {
+        (TokenKind::EOF, "".to_string())
+    }
+
+    #[test]
+    fn test_command_words() {
+        assert_eq!(
+            kinds_and_values("ls -la /tmp/*.rs --color=auto"),
+            vec![
+                word("ls"),
+                word("-la"),
+                word("/tmp/*.rs"),
+                word("--color=auto"),
+                eof()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hash_comments() {
+        let comment = |text: &str| (TokenKind::Comment, text.to_string());
+        let newline = || {
+            (
+                TokenKind::Punctuation(PunctuationTokenKind::Newline),
+                "\n".to_string(),
+            )
+        };
+        assert_eq!(
+            kinds_and_values("#!/usr/bin/env sosh\n# note\necho a#b # rest; ls\n"),
+            vec![
+                comment("#!/usr/bin/env sosh"),
+                comment("# note"),
+                word("echo"),
+                word("a#b"),
+                comment("# rest; ls"),
+                newline(),
+                eof()
+            ]
+        );
+        assert_eq!(
+            kinds_and_values("let x = 1 # one"),
+            vec![
+                (TokenKind::Keyword(KeywordTokenKind::Let), "let".to_string()),
+                (
+                    TokenKind::Literal(LiteralTokenKind::Identifier),
+                    "x".to_string()
+                ),
+                (
+                    TokenKind::Operator(OperatorTokenKind::Assign),
+                    "=".to_string()
+                ),
+                (
+                    TokenKind::Literal(LiteralTokenKind::Number),
+                    "1".to_string()
+                ),
+                comment("# one"),
+                eof()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_command_quoting_keeps_raw_words() {
+        assert_eq!(
+            kinds_and_values(r#"echo 'a b' "c \" d" e\ f ./x.sh~ $HOME"#),
+            vec![
+                word("echo"),
+                word("'a b'"),
+                word(r#""c \" d""#),
+                word(r"e\ f"),
+                word("./x.sh~"),
+                word("$HOME"),
+                eof()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_command_operators_and_line_continuation() {
+        assert_eq!(
+            kinds_and_values("cat < in \\\n | sort >> out &"),
+            vec![
+                word("cat"),
+                (
+                    TokenKind::Operator(OperatorTokenKind::Less),
+                    "<".to_string()
+                ),
+                word("in"),
+                (TokenKind::Operator(OperatorTokenKind::Or), "|".to_string()),
+                word("sort"),
+                (
+                    TokenKind::Operator(OperatorTokenKind::DoubleGreater),
+                    ">>".to_string()
+                ),
+                word("out"),
+                (TokenKind::Operator(OperatorTokenKind::And), "&".to_string()),
+                eof()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_command_redirections_with_descriptors() {
+        let greater = |value: &str| {
+            (
+                TokenKind::Operator(OperatorTokenKind::Greater),
+                value.to_string(),
+            )
+        };
+        assert_eq!(
+            kinds_and_values("make 2>err 2>&1 &>all 3<&0 x2>y"),
+            vec![
+                word("make"),
+                greater("2>"),
+                word("err"),
+                greater("2>&"),
+                word("1"),
+                greater("&>"),
+                word("all"),
+                (
+                    TokenKind::Operator(OperatorTokenKind::Less),
+                    "3<&".to_string()
+                ),
+                word("0"),
+                word("x2"),
+                greater(">"),
+                word("y"),
+                eof()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_here_documents() {
+        let mut tokenizer =
+            Tokenizer::new("cat <<EOF <<-'END' | wc\nhi $x\nEOF\n\tkeep\n\tEND\nls");
+        let tokens: Vec<(TokenKind, String)> = tokenizer
+            .tokenize()
+            .into_iter()
+            .map(|token| (token.kind, token.value))
+            .collect();
+        let here_doc = |body: &str| {
+            (
+                TokenKind::Literal(LiteralTokenKind::HereDoc),
+                body.to_string(),
+            )
+        };
+        assert_eq!(
+            tokens,
+            vec![
+                word("cat"),
+                (
+                    TokenKind::Operator(OperatorTokenKind::DoubleLess),
+                    "<<".to_string()
+                ),
+                word("EOF"),
+                here_doc("hi $x\n"),
+                (
+                    TokenKind::Operator(OperatorTokenKind::DoubleLess),
+                    "<<-".to_string()
+                ),
+                word("'END'"),
+                here_doc("keep\n"),
+                (TokenKind::Operator(OperatorTokenKind::Or), "|".to_string()),
+                word("wc"),
+                (
+                    TokenKind::Punctuation(PunctuationTokenKind::Newline),
+                    "\n".to_string()
+                ),
+                word("ls"),
+                eof()
+            ]
+        );
+        assert!(!tokenizer.needs_more_input());
+
+        let mut tokenizer = Tokenizer::new("cat <<EOF\nstill typing");
+        tokenizer.tokenize();
+        assert!(tokenizer.needs_more_input());
+    }
+
+    #[test]
+    fn test_mixed_commands_and_script() {
+        let tokens = kinds_and_values("let x = 1; if (x == 1) { echo yes }\nx + 1\nFOO=bar env");
+        let expected = vec![
+            (TokenKind::Keyword(KeywordTokenKind::Let), "let".to_string()),
+            (
+                TokenKind::Literal(LiteralTokenKind::Identifier),
+                "x".to_string(),
+            ),
+            (
+                TokenKind::Operator(OperatorTokenKind::Assign),
+                "=".to_string(),
+            ),
+            (
+                TokenKind::Literal(LiteralTokenKind::Number),
+                "1".to_string(),
+            ),
+            (
+                TokenKind::Punctuation(PunctuationTokenKind::Semicolon),
+                ";".to_string(),
+            ),
+            (TokenKind::Keyword(KeywordTokenKind::If), "if".to_string()),
+            (
+                TokenKind::Punctuation(PunctuationTokenKind::LParen),
+                "(".to_string(),
+            ),
+            (
+                TokenKind::Literal(LiteralTokenKind::Identifier),
+                "x".to_string(),
+            ),
+            (
+                TokenKind::Operator(OperatorTokenKind::Equal),
+                "==".to_string(),
+            ),
+            (
+                TokenKind::Literal(LiteralTokenKind::Number),
+                "1".to_string(),
+            ),
+            (
+                TokenKind::Punctuation(PunctuationTokenKind::RParen),
+                ")".to_string(),
+            ),
+            (
+                TokenKind::Punctuation(PunctuationTokenKind::LBrace),
+                "{".to_string(),
+            ),
+            word("echo"),
+            word("yes"),
+            (
+                TokenKind::Punctuation(PunctuationTokenKind::RBrace),
+                "}".to_string(),
+            ),
+            (
+                TokenKind::Literal(LiteralTokenKind::Identifier),
+                "x".to_string(),
+            ),
+            (
+                TokenKind::Operator(OperatorTokenKind::Plus),
+                "+".to_string(),
+            ),
+            (
+                TokenKind::Literal(LiteralTokenKind::Number),
+                "1".to_string(),
+            ),
+            (
+                TokenKind::Punctuation(PunctuationTokenKind::Newline),
+                "\n".to_string(),
+            ),
+            word("FOO=bar"),
+            word("env"),
+            eof(),
+        ];
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn test_ambiguous_statement_starts() {
+        let first_kind = |input: &str| kinds_and_values(input)[0].0;
+        let word_kind = TokenKind::Literal(LiteralTokenKind::Word);
+
+        assert_eq!(first_kind("cat -"), word_kind);
+        assert_eq!(first_kind("ls *"), word_kind);
+        assert_eq!(first_kind("apt-get install"), word_kind);
+        assert_eq!(first_kind("x"), word_kind);
+        assert_eq!(first_kind("7z x file.7z"), word_kind);
+        assert_ne!(first_kind("x - 1"), word_kind);
+        assert_ne!(first_kind("x = 1"), word_kind);
+        assert_ne!(first_kind("print(1)"), word_kind);
+        assert_ne!(first_kind("-5 + 1"), word_kind);
+
+        let string_kind = TokenKind::Literal(LiteralTokenKind::String);
+        assert_eq!(first_kind("\"a\" + \"b\""), string_kind);
+        assert_eq!(first_kind("\"a\" == \"b\""), string_kind);
+        assert_eq!(first_kind("\"a b\" - 1"), string_kind);
+        assert_eq!(first_kind("\"my tool\" --help"), word_kind);
+        assert_eq!(first_kind("\"$HOME\"/bin/tool -v"), word_kind);
+        assert_eq!(first_kind("\"ls\""), word_kind);
+    }
+
+    #[test]
+    fn test_token_spans() {
+        let input = "let s = \"a b\" // note\ncat <<EOF | wc\nbody\nEOF\n";
+        let spans: Vec<(TokenKind, &str)> = Tokenizer::new(input)
+            .tokenize_with_spans()
+            .into_iter()
+            .map(|(token, span)| (token.kind, &input[span]))
+            .collect();
+
+        let texts: Vec<&str> = spans.iter().map(|(_, text)| *text).collect();
+        assert_eq!(
+            texts,
+            [
+                "let",
+                "s",
+                "=",
+                "\"a b\"",
+                "// note",
+                "\n",
+                "cat",
+                "<<",
+                "EOF",
+                "body\nEOF\n",
+                "|",
+                "wc",
+                "\n",
+                ""
+            ]
+        );
+        assert_eq!(spans[9].0, TokenKind::Literal(LiteralTokenKind::HereDoc));
+        assert_eq!(spans[13].0, TokenKind::EOF);
+    }
 }
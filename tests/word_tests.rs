@@ -0,0 +1,114 @@
+use sheesh::interpreter::word::*;
+use std::fs;
+
+fn lookup(name: &str) -> Option<String> {
+    match name {
+        "HOME" => Some("/home/sopy".to_string()),
+        "greeting" => Some("hello world".to_string()),
+        "star" => Some("*".to_string()),
+        "?" => Some("0".to_string()),
+        "1" => Some("first".to_string()),
+        "#" => Some("2".to_string()),
+        "@" => Some("first second".to_string()),
+        _ => None,
+    }
+}
+
+fn expand(raw: &str) -> Vec<String> {
+    expand_word(raw, &lookup)
+}
+
+#[test]
+fn test_quote_removal() {
+    assert_eq!(expand("plain"), vec!["plain"]);
+    assert_eq!(expand("'a b'"), vec!["a b"]);
+    assert_eq!(expand(r#""a \"b\" \n""#), vec![r#"a "b" \n"#]);
+    assert_eq!(expand(r"a\ b\*"), vec!["a b*"]);
+    assert_eq!(expand("''"), vec![""]);
+}
+
+#[test]
+fn test_variable_expansion() {
+    assert_eq!(expand("$greeting"), vec!["hello world"]);
+    assert_eq!(expand(r#""[${greeting}]""#), vec!["[hello world]"]);
+    assert_eq!(expand("'$greeting'"), vec!["$greeting"]);
+    assert_eq!(expand("status=$?"), vec!["status=0"]);
+    assert_eq!(expand("cost$"), vec!["cost$"]);
+    assert_eq!(expand("$1:$#"), vec!["first:2"]);
+    assert_eq!(expand("[$@]"), vec!["[first second]"]);
+    assert_eq!(expand("${1}0"), vec!["first0"]);
+    // an unquoted empty expansion disappears, a quoted one stays as an empty argument
+    assert_eq!(expand("$unset"), Vec::<String>::new());
+    assert_eq!(expand("\"$unset\""), vec![""]);
+}
+
+#[test]
+fn test_here_doc_expansion() {
+    assert_eq!(
+        expand_here_doc("say \"$greeting\" to '${HOME}'\n", &lookup),
+        "say \"hello world\" to '/home/sopy'\n"
+    );
+    assert_eq!(
+        expand_here_doc("\\$greeting \\\\ \\n \\\nend", &lookup),
+        "$greeting \\ \\n end"
+    );
+}
+
+#[test]
+fn test_tilde_expansion() {
+    assert_eq!(expand("~"), vec!["/home/sopy"]);
+    assert_eq!(expand("~/src"), vec!["/home/sopy/src"]);
+    assert_eq!(expand("'~'"), vec!["~"]);
+    assert_eq!(expand("a~"), vec!["a~"]);
+}
+
+#[test]
+fn test_glob_match() {
+    assert!(glob_match("*.rs", "main.rs"));
+    assert!(!glob_match("*.rs", "main.rsx"));
+    assert!(glob_match("?at", "cat"));
+    assert!(glob_match("[a-c]at", "bat"));
+    assert!(!glob_match("[!a-c]at", "bat"));
+    assert!(glob_match(r"\*", "*"));
+    assert!(!glob_match(r"\*", "x"));
+    assert!(glob_match("[x", "[x"));
+}
+
+#[test]
+fn test_glob_expansion() {
+    let directory = std::env::temp_dir().join(format!("sosh-glob-{}", std::process::id()));
+    fs::create_dir_all(directory.join("sub")).unwrap();
+    for name in ["b.rs", "a.rs", ".hidden.rs", "c.txt", "sub/d.rs"] {
+        fs::write(directory.join(name), "").unwrap();
+    }
+    let base = directory.to_str().unwrap();
+
+    assert_eq!(
+        expand(&format!("{}/*.rs", base)),
+        vec![format!("{}/a.rs", base), format!("{}/b.rs", base)]
+    );
+    assert_eq!(
+        expand(&format!("{}/.*.rs", base)),
+        vec![format!("{}/.hidden.rs", base)]
+    );
+    assert_eq!(
+        expand(&format!("{}/*/*.rs", base)),
+        vec![format!("{}/sub/d.rs", base)]
+    );
+    assert_eq!(
+        expand(&format!("{}/*/", base)),
+        vec![format!("{}/sub/", base)]
+    );
+    // quoted or substituted metacharacters and patterns without matches stay literal
+    assert_eq!(
+        expand(&format!("'{}/*.rs'", base)),
+        vec![format!("{}/*.rs", base)]
+    );
+    assert_eq!(expand("$star"), vec!["*"]);
+    assert_eq!(
+        expand(&format!("{}/*.md", base)),
+        vec![format!("{}/*.md", base)]
+    );
+
+    fs::remove_dir_all(directory).unwrap();
+}